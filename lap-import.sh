@@ -2,21 +2,10 @@
 
 # Lapwing drill list import.
 
-sqlite3 learn.db <<ZZZ
-DELETE FROM lesson;
-DELETE FROM list;
-ZZZ
-
-# The lapwing entries are are lists of strokes a tab and a translation.
+# The lapwing entries are lists of words, a tab, and the strokes.  The list name comes from the
+# file name.  Lists that have already been imported are updated in place.
 lap() {
-    echo $1 > tmpfile
-    echo '' >> tmpfile
-    sed s'/^\(.*\)\t\(.*\)/'"'"'\1'"'"': \2/' \
-        < lapwing/$1.txt \
-        >> tmpfile
-
-    cargo r -- import --db learn.db tmpfile
-    rm tmpfile
+    cargo r -- import --db learn.db --update --format tsv lapwing/$1.txt
 }
 
 # Make the briefs first, especially to make them easier to eliminate from other definitions.
@@ -0,0 +1,7 @@
+-- Words can have alternate outlines that are also accepted.  These are
+-- kept, separated by '|', in a new column of both the lesson and learn
+-- tables.
+
+ALTER TABLE lesson ADD COLUMN alts TEXT NOT NULL DEFAULT '';
+ALTER TABLE learn ADD COLUMN alts TEXT NOT NULL DEFAULT '';
+UPDATE schema SET version = '2026-10-16a';
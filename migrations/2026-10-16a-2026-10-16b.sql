@@ -0,0 +1,36 @@
+-- Learning progress is tracked for each word and outline, rather than
+-- just for each word, as the same text can appear in the lessons with
+-- different outlines.  The errors record the outline that was expected,
+-- so they can refer to the learn entry.
+
+ALTER TABLE learn RENAME TO learnold;
+CREATE TABLE learn (
+  word TEXT NOT NULL,
+  steno TEXT NOT NULL,
+  goods INTEGER NOT NULL,
+  interval REAL NOT NULL,
+  factor REAL NOT NULL,
+  next REAL NOT NULL,
+  alts TEXT NOT NULL DEFAULT '',
+  PRIMARY KEY (word, steno));
+INSERT INTO learn SELECT word, steno, goods, interval, factor, next, alts FROM learnold WHERE true;
+DROP TABLE learnold;
+CREATE INDEX IF NOT EXISTS learn_steno_idx ON learn (steno);
+CREATE INDEX IF NOT EXISTS learn_next_idx ON learn (next);
+
+ALTER TABLE errors RENAME TO errorsold;
+CREATE TABLE errors (
+  stamp DATETIME NOT NULL,
+  word TEXT NOT NULL,
+  goods INTEGER NOT NULL,
+  interval REAL NOT NULL,
+  next REAL NOT NULL,
+  actual TEXT NOT NULL,
+  steno TEXT NOT NULL,
+  FOREIGN KEY (word, steno) REFERENCES learn (word, steno));
+INSERT INTO errors
+  SELECT stamp, errorsold.word, errorsold.goods, errorsold.interval, errorsold.next, actual,
+    COALESCE(learn.steno, '')
+  FROM errorsold LEFT JOIN learn USING (word) WHERE true;
+DROP TABLE errorsold;
+UPDATE schema SET version = '2026-10-16b';
@@ -0,0 +1,16 @@
+-- Lists can be grouped into the chapters of a course, imported from a
+-- course manifest.  Lists that aren't part of a course have a NULL
+-- chapter.
+
+CREATE TABLE course (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL);
+CREATE TABLE chapter (
+    id INTEGER PRIMARY KEY,
+    course INTEGER REFERENCES course (id) NOT NULL,
+    name TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    UNIQUE (course, seq));
+ALTER TABLE list ADD COLUMN chapter INTEGER REFERENCES chapter (id);
+ALTER TABLE list ADD COLUMN seq INTEGER;
+UPDATE schema SET version = '2026-10-16c';
@@ -0,0 +1,7 @@
+-- Lesson entries can have a label shown in place of the word, a note
+-- about the word, and a comma separated list of tags.
+
+ALTER TABLE lesson ADD COLUMN label TEXT;
+ALTER TABLE lesson ADD COLUMN note TEXT;
+ALTER TABLE lesson ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+UPDATE schema SET version = '2026-10-16d';
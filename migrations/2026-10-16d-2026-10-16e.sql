@@ -0,0 +1,10 @@
+-- Each database records the steno system its lessons are written in.
+-- A NULL definition is one of the built-in systems, found by name,
+-- otherwise the definition is kept in the database.  Existing databases
+-- were all written with the English system.
+
+CREATE TABLE system (
+    name TEXT NOT NULL,
+    definition TEXT);
+INSERT INTO system VALUES ('english', NULL);
+UPDATE schema SET version = '2026-10-16e';
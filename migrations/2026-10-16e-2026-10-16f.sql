@@ -0,0 +1,13 @@
+-- The time taken to write each word, as hesitation is a sign of a word
+-- that isn't yet remembered well.  'first' is the time from when the
+-- word was shown to its first stroke, and 'gaps' is a JSON array of the
+-- times between its strokes, which is empty for words of one stroke.
+
+CREATE TABLE latency (
+    stamp DATETIME NOT NULL,
+    word TEXT NOT NULL,
+    steno TEXT NOT NULL,
+    first REAL NOT NULL,
+    gaps TEXT NOT NULL,
+    corrections INTEGER NOT NULL);
+UPDATE schema SET version = '2026-10-16f';
@@ -0,0 +1,254 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Checking of lesson files.
+//!
+//! Lessons are loaded just as they would be for an import, and all of the problems found are
+//! reported, even in a file that has errors.  Lines that fail to parse are errors.  Questionable
+//! strokes, lines skipped because they don't look like entries, words given more than one outline,
+//! and outlines shared by more than one word are warnings.  The last two are also checked against
+//! the lists already in the database, if one is given.  Entries with a label are checked by their
+//! label, as that is what tells them apart.
+
+use crate::db::Db;
+use crate::lessons::{Filter, Format, Lesson};
+use crate::stroke::Mode;
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// The outcome of checking.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl Report {
+    /// Did the lessons pass the check?  In strict mode, warnings also fail it.
+    pub fn passed(&self, strict: bool) -> bool {
+        self.errors == 0 && !(strict && self.warnings > 0)
+    }
+}
+
+// A single entry, from either a file or the database.
+struct Item {
+    // The word, or its label if it has one.
+    word: String,
+    steno: String,
+    // The canonical outline, followed by the alternates.
+    outlines: Vec<String>,
+    // Where this entry came from, for messages.
+    place: String,
+    // Did this come from a file being checked?
+    checked: bool,
+}
+
+/// Check the given lesson files, printing any problems found.
+pub fn check(files: &[String], format: Option<Format>, db: Option<&mut Db>) -> Result<Report> {
+    let mut report = Report::default();
+    let mut items = vec![];
+    let mut names = vec![];
+
+    for file in files {
+        let lesson = match Lesson::read(file, format, None, &Filter::default(), Mode::Lenient) {
+            Ok((lesson, errors)) => {
+                for error in &errors {
+                    println!("error: {}", error);
+                    report.errors += 1;
+                }
+                lesson
+            }
+            Err(e) => {
+                println!("error: {}: {}", file, e);
+                report.errors += 1;
+                continue;
+            }
+        };
+
+        for warning in &lesson.warnings {
+            println!("warning: {}", warning);
+            report.warnings += 1;
+        }
+
+        for entry in &lesson.entries {
+            let mut outlines = vec![entry.steno.to_string()];
+            outlines.extend(entry.alternates.iter().map(|alt| alt.to_string()));
+            items.push(Item {
+                word: entry.label.clone().unwrap_or_else(|| entry.word.clone()),
+                steno: outlines[0].clone(),
+                outlines,
+                place: if entry.line > 0 {
+                    format!("{}:{}", file, entry.line)
+                } else {
+                    file.clone()
+                },
+                checked: true,
+            });
+        }
+        names.push(lesson.description);
+    }
+
+    // Lists in the database with the same name as a file being checked are presumably an earlier
+    // import of that file, and are left out.
+    if let Some(db) = db {
+        for entry in db.get_lesson_entries()? {
+            if names.contains(&entry.list) {
+                continue;
+            }
+            let mut outlines = vec![entry.steno.clone()];
+            outlines.extend(
+                entry
+                    .alts
+                    .split('|')
+                    .map(|alt| alt.trim().to_string())
+                    .filter(|alt| !alt.is_empty()),
+            );
+            items.push(Item {
+                word: entry.label.unwrap_or(entry.word),
+                steno: entry.steno,
+                outlines,
+                place: format!("list {} ({})", entry.listid, entry.list),
+                checked: false,
+            });
+        }
+    }
+
+    // Words that are given different outlines.
+    for (word, group) in group_by(&items, |item| vec![item.word.clone()]) {
+        if !group.iter().any(|item| item.checked) {
+            continue;
+        }
+        let mut stenos: Vec<&str> = group.iter().map(|item| item.steno.as_str()).collect();
+        stenos.sort_unstable();
+        stenos.dedup();
+        if stenos.len() > 1 {
+            let places: Vec<_> = group
+                .iter()
+                .map(|item| format!("{} ({})", item.steno, item.place))
+                .collect();
+            println!("warning: {:?} has different outlines: {}", word, places.join(", "));
+            report.warnings += 1;
+        }
+    }
+
+    // Outlines that are used for more than one word.
+    for (outline, group) in group_by(&items, |item| item.outlines.clone()) {
+        if !group.iter().any(|item| item.checked) {
+            continue;
+        }
+        let mut words: Vec<&str> = group.iter().map(|item| item.word.as_str()).collect();
+        words.sort_unstable();
+        words.dedup();
+        if words.len() > 1 {
+            let places: Vec<_> = group
+                .iter()
+                .map(|item| format!("{:?} ({})", item.word, item.place))
+                .collect();
+            println!("warning: {} is used for different words: {}", outline, places.join(", "));
+            report.warnings += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+// Group the items by the keys returned by `keys`, in the order the keys are first seen.  An item
+// can be in several groups.
+fn group_by<F>(items: &[Item], keys: F) -> Vec<(String, Vec<&Item>)>
+where
+    F: Fn(&Item) -> Vec<String>,
+{
+    let mut result: Vec<(String, Vec<&Item>)> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for item in items {
+        for key in keys(item) {
+            match index.get(&key) {
+                Some(&pos) => result[pos].1.push(item),
+                None => {
+                    index.insert(key.clone(), result.len());
+                    result.push((key, vec![item]));
+                }
+            }
+        }
+    }
+    result
+}
+
+// Check lesson files with the given contents, returning the report.
+#[cfg(test)]
+fn check_texts(texts: &[&str], db: Option<&mut Db>) -> Report {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Tests run in parallel, so each file needs its own name.
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir();
+    let files: Vec<String> = texts
+        .iter()
+        .map(|text| {
+            let num = NEXT.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("sdrill-check-{}-{}.txt", std::process::id(), num));
+            std::fs::write(&path, text).unwrap();
+            path.to_string_lossy().into_owned()
+        })
+        .collect();
+    let report = check(&files, None, db).unwrap();
+    for file in files {
+        std::fs::remove_file(file).unwrap();
+    }
+    report
+}
+
+#[test]
+fn check_lessons() {
+    let counts = |report: Report| (report.errors, report.warnings);
+
+    let clean = "Animals\n\n'cat': KAT\n'dog': TKOG\n";
+    assert_eq!(counts(check_texts(&[clean], None)), (0, 0));
+
+    // A questionable stroke, and a line that looks like an entry but isn't quoted.
+    assert_eq!(counts(check_texts(&["Ten\n\n'ten': TEPB | #S-Z\n"], None)), (0, 1));
+    assert_eq!(counts(check_texts(&["Cat\n\ncat: KAT\n"], None)), (0, 1));
+
+    // A word with two outlines, and an outline for two words, across files.
+    assert_eq!(counts(check_texts(&[clean, "More\n\n'cat': KA*T\n"], None)), (0, 1));
+    assert_eq!(counts(check_texts(&[clean, "More\n\n'kat': KAT\n"], None)), (0, 1));
+
+    // Labels tell apart words that share an outline.
+    let labelled = "Quotes\tKW-GS\t\" (open)\n\"\tKR-GS\t\" (close)\n";
+    assert_eq!(counts(check_texts(&[labelled], None)), (0, 0));
+
+    // Errors don't hide the other problems in the same file.
+    let broken = "Broken\n\n'bad': STKQ\n'worse': KWQ\n'ten': #S-Z\n'cat': KAT\n";
+    let report = check_texts(&[broken, clean], None);
+    assert_eq!(counts(report), (2, 1));
+
+    // Only warnings fail a strict check.
+    let report = Report { errors: 0, warnings: 1 };
+    assert!(report.passed(false));
+    assert!(!report.passed(true));
+    assert!(!Report { errors: 1, warnings: 0 }.passed(false));
+    assert!(Report::default().passed(true));
+}
+
+#[test]
+fn check_against_db() {
+    use crate::lessons::Entry;
+    use crate::stroke::StenoPhrase;
+
+    let path = std::env::temp_dir().join(format!("sdrill-check-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    Db::init(&path, "english").unwrap();
+    let mut db = Db::open(&path).unwrap();
+    db.load(&Lesson {
+        description: "Animals".to_string(),
+        entries: vec![Entry::new("cat".to_string(), StenoPhrase::parse("KAT").unwrap(), vec![])],
+        warnings: vec![],
+    })
+    .unwrap();
+
+    // The outline is already used for another word.
+    assert_eq!(check_texts(&["Other\n\n'kat': KAT\n"], Some(&mut db)).warnings, 1);
+    // A list with the same name is taken to be an earlier import of the file.
+    assert_eq!(check_texts(&["Animals\n\n'cat': KA*T\n"], Some(&mut db)).warnings, 0);
+
+    drop(db);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,133 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Course manifests.
+//!
+//! A course is a whole curriculum of lessons, described by a TOML manifest, so that it can be
+//! imported in one go, with the lessons in the right order and grouped into chapters.
+//!
+//! ```toml
+//! name = "Lapwing"
+//! format = "tsv"
+//!
+//! [[chapter]]
+//! name = "Chapter 1: One syllable words"
+//! lessons = [
+//!     { file = "lapwing/1.1.txt", name = "1.1 Left hand" },
+//!     { file = "lapwing/1.2.txt" },
+//! ]
+//! ```
+//!
+//! Lesson files are relative to the directory holding the manifest.  The `name` of a lesson
+//! overrides the name it would otherwise be given, and `format` can be given for the whole course
+//! or for a single lesson.
+
+use crate::lessons::{Filter, Format, Lesson};
+use crate::stroke::Mode;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Course {
+    pub name: String,
+    format: Option<String>,
+    #[serde(rename = "chapter")]
+    pub chapters: Vec<Chapter>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Chapter {
+    pub name: String,
+    pub lessons: Vec<CourseLesson>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CourseLesson {
+    pub file: PathBuf,
+    pub name: Option<String>,
+    format: Option<String>,
+}
+
+impl Course {
+    /// Read a course manifest.  The lesson file names are made relative to the directory of the
+    /// manifest.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Course> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut course: Course =
+            toml::from_str(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for chapter in &mut course.chapters {
+            for lesson in &mut chapter.lessons {
+                lesson.file = dir.join(&lesson.file);
+            }
+        }
+        Ok(course)
+    }
+
+    /// Load all of the lessons of the course, grouped by chapter.  The format of each lesson is
+    /// taken from the lesson, then the course, then `format`, and is otherwise detected.
+    pub fn load_lessons(
+        &self,
+        format: Option<Format>,
+        filter: &Filter,
+        mode: Mode,
+    ) -> Result<Vec<Vec<Lesson>>> {
+        let course_format = self.format.as_deref().map(str::parse).transpose()?;
+        let mut result = vec![];
+        for chapter in &self.chapters {
+            let mut lessons = vec![];
+            for item in &chapter.lessons {
+                let lesson_format = item.format.as_deref().map(str::parse).transpose()?;
+                println!("import: {}", item.file.display());
+                let lesson = Lesson::load(
+                    &item.file,
+                    lesson_format.or(course_format).or(format),
+                    item.name.as_deref(),
+                    filter,
+                    mode,
+                )?;
+                for warning in &lesson.warnings {
+                    println!("warning: {}", warning);
+                }
+                lessons.push(lesson);
+            }
+            result.push(lessons);
+        }
+        Ok(result)
+    }
+}
+
+#[test]
+fn parse_manifest() {
+    let course: Course = toml::from_str(
+        r#"
+        name = "Lapwing"
+        format = "tsv"
+
+        [[chapter]]
+        name = "One"
+        lessons = [
+            { file = "1.1.txt", name = "Left hand" },
+            { file = "1.2.txt", format = "typey" },
+        ]
+
+        [[chapter]]
+        name = "Two"
+        [[chapter.lessons]]
+        file = "2.1.txt"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(course.name, "Lapwing");
+    assert_eq!(course.chapters.len(), 2);
+    assert_eq!(course.chapters[0].lessons[0].name.as_deref(), Some("Left hand"));
+    assert_eq!(course.chapters[0].lessons[1].format.as_deref(), Some("typey"));
+    assert_eq!(course.chapters[1].lessons[0].file, Path::new("2.1.txt"));
+
+    assert!(toml::from_str::<Course>("name = \"x\"\nchapters = []\n").is_err());
+}
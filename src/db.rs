@@ -1,37 +1,65 @@
 // SPDX-License-Identifier: GPL-3.0
 //! Learning database operations.
 
+use crate::stroke::system::{self, System};
 use crate::stroke::StenoPhrase;
-use crate::Lesson;
+use crate::course::Course;
+use crate::lessons::{Entry, Lesson};
 use crate::ui::NewList;
 use anyhow::{anyhow, bail, Result};
 use log::info;
-use rusqlite::{named_params, Connection};
+use rusqlite::backup::Progress;
+use rusqlite::{named_params, Connection, DatabaseName, OptionalExtension};
 use std::collections::HashMap;
 use std::path::Path;
 use std::time::SystemTime;
 
-/// The schema version that matches this code.  May be usable in the future for automatic upgrades.
-static SCHEMA_VERSION: &str = "2023-11-10a";
+mod migrate;
+
+/// The schema version that matches this code.  Databases at older versions are upgraded when
+/// opened.
+static SCHEMA_VERSION: &str = "2026-10-16f";
 
 static SCHEMA: &[&str] = &[
+    // Progress is tracked for each word and outline, as the same text can appear with different
+    // outlines.
     "CREATE TABLE learn (
-        word TEXT UNIQUE PRIMARY KEY,
+        word TEXT NOT NULL,
         steno TEXT NOT NULL,
         goods INTEGER NOT NULL,
         interval REAL NOT NULL,
         factor REAL NOT NULL,
-        next REAL NOT NULL);",
+        next REAL NOT NULL,
+        alts TEXT NOT NULL DEFAULT '',
+        PRIMARY KEY (word, steno));",
     "CREATE INDEX learn_steno_idx ON learn (steno);",
     "CREATE INDEX learn_next_idx ON learn (next);",
+    // Lists imported as part of a course are in a chapter, at position 'seq' within it.
     "CREATE TABLE list (
+        id INTEGER PRIMARY KEY,
+        name TEXT UNIQUE NOT NULL,
+        chapter INTEGER REFERENCES chapter (id),
+        seq INTEGER);",
+    "CREATE TABLE course (
         id INTEGER PRIMARY KEY,
         name TEXT UNIQUE NOT NULL);",
+    "CREATE TABLE chapter (
+        id INTEGER PRIMARY KEY,
+        course INTEGER REFERENCES course (id) NOT NULL,
+        name TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        UNIQUE (course, seq));",
+    // 'alts' holds the alternate outlines that are also accepted, separated by '|'.  'label' is
+    // shown instead of the word, if set, and 'tags' are separated by ','.
     "CREATE TABLE lesson (
         word TEXT NOT NULL,
         steno TEXT NOT NULL,
         listid INTEGER REFERENCES list (id) NOT NULL,
         seq INTEGER NOT NULL,
+        alts TEXT NOT NULL DEFAULT '',
+        label TEXT,
+        note TEXT,
+        tags TEXT NOT NULL DEFAULT '',
         UNIQUE (listid, seq));",
     // The history.  If 'stop' is null, then we didn't exit successfully.
     "CREATE TABLE history (
@@ -39,13 +67,28 @@ static SCHEMA: &[&str] = &[
         start DATETIME NOT NULL,
         stop DATETIME);",
     "CREATE TABLE schema (version TEXT NOT NULL);",
+    // The steno system.  A NULL definition is a built-in system, found by name.
+    "CREATE TABLE system (
+        name TEXT NOT NULL,
+        definition TEXT);",
     "CREATE TABLE errors (
         stamp DATETIME NOT NULL,
-        word TEXT REFERENCES learn (word) NOT NULL,
+        word TEXT NOT NULL,
         goods INTEGER NOT NULL,
         interval REAL NOT NULL,
         next REAL NOT NULL,
-        actual TEXT NOT NULL);",
+        actual TEXT NOT NULL,
+        steno TEXT NOT NULL,
+        FOREIGN KEY (word, steno) REFERENCES learn (word, steno));",
+    // The time taken to write each word: from when it was shown to the first stroke, and each gap
+    // between its strokes, as a JSON array.
+    "CREATE TABLE latency (
+        stamp DATETIME NOT NULL,
+        word TEXT NOT NULL,
+        steno TEXT NOT NULL,
+        first REAL NOT NULL,
+        gaps TEXT NOT NULL,
+        corrections INTEGER NOT NULL);",
 ];
 
 pub struct Db {
@@ -54,8 +97,11 @@ pub struct Db {
 
 impl Db {
     /// Initialize a new database.  The file shouldn't exist, and will likely generate an error if
-    /// it does.
-    pub fn init<P: AsRef<Path>>(path: P) -> Result<()> {
+    /// it does.  The `system` is either the name of a built-in steno system, or a file with the
+    /// definition of one, which is kept in the database.
+    pub fn init<P: AsRef<Path>>(path: P, system: &str) -> Result<()> {
+        let (system, definition) = System::find(system)?;
+
         let mut conn = Connection::open(path)?;
         let tx = conn.transaction()?;
 
@@ -66,54 +112,181 @@ impl Db {
             "INSERT INTO schema (version) VALUES (:version)",
             &[(":version", SCHEMA_VERSION)],
         )?;
+        tx.execute(
+            "INSERT INTO system (name, definition) VALUES (:name, :definition)",
+            named_params! {
+                ":name": &system.name,
+                ":definition": &definition,
+            },
+        )?;
         tx.commit()?;
         Ok(())
     }
 
-    /// Open the database
+    /// Open the database.  If the database has an older schema, a backup copy is made, and it is
+    /// upgraded to the current schema.  The database's steno system becomes the one strokes are
+    /// read and shown with.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Db> {
-        let conn = Connection::open(path)?;
-        let version: String = conn.query_row("SELECT version FROM schema", [], |row| row.get(0))?;
+        let path = path.as_ref();
+        let mut conn = Connection::open(path)?;
+        let version = migrate::get_version(&conn)?;
         if version != SCHEMA_VERSION {
-            bail!(
-                "Schema version mismatch: found {}, want {}",
+            let steps = migrate::plan(&version)?;
+            let backup = migrate::backup(&conn, path, &version)?;
+            println!(
+                "Upgrading database from {} to {}, previous version saved in {}",
                 version,
-                SCHEMA_VERSION
+                SCHEMA_VERSION,
+                backup.display()
             );
+            migrate::apply(&mut conn, &steps)?;
         }
+        Db::with_system(conn)
+    }
+
+    /// Open a copy of the database, held in memory, so that nothing done with it changes the file.
+    /// This is used when replaying a session, which shouldn't count as practice.  The copy is
+    /// upgraded to the current schema, if needed, without changing the file.
+    pub fn open_scratch<P: AsRef<Path>>(path: P) -> Result<Db> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.restore(DatabaseName::Main, path, None::<fn(Progress)>)?;
+        let version = migrate::get_version(&conn)?;
+        let steps = migrate::plan(&version)?;
+        migrate::apply(&mut conn, &steps)?;
+        Db::with_system(conn)
+    }
+
+    // Make the database's steno system the one strokes are read and shown with.
+    fn with_system(conn: Connection) -> Result<Db> {
+        let (name, definition): (String, Option<String>) =
+            conn.query_row("SELECT name, definition FROM system", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        let text = match &definition {
+            Some(text) => text.as_str(),
+            None => System::builtin(&name)
+                .ok_or_else(|| anyhow!("Unknown steno system {:?} in database", name))?,
+        };
+        system::set_current(System::parse(text)?);
 
         Ok(Db { conn })
     }
 
+    /// Show what would be done to upgrade the database to the current schema, without changing
+    /// it.
+    pub fn migrate_dry_run<P: AsRef<Path>>(path: P) -> Result<()> {
+        let conn = Connection::open(path)?;
+        let version = migrate::get_version(&conn)?;
+        let steps = migrate::plan(&version)?;
+        if steps.is_empty() {
+            println!("Database is at the current version {}", version);
+            return Ok(());
+        }
+
+        println!("Database is at {}, would upgrade to {}", version, SCHEMA_VERSION);
+        for step in steps {
+            println!();
+            println!("{} -> {}:", step.from, step.to);
+            for line in step.sql.lines() {
+                println!("    {}", line);
+            }
+        }
+        Ok(())
+    }
+
     /// Load the words from the given lesson into the database.
     pub fn load(&mut self, lesson: &Lesson) -> Result<()> {
+        if let Some(id) = self.find_list(&lesson.description)? {
+            bail!(
+                "List {:?} already exists (id {}), use --update to re-import it",
+                lesson.description,
+                id
+            );
+        }
+
+        let tx = self.conn.transaction()?;
+        insert_lesson(&tx, lesson)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Find the ID of the list with the given name.
+    pub fn find_list(&mut self, name: &str) -> Result<Option<usize>> {
+        find_list(&self.conn, name)
+    }
+
+    /// Re-import a lesson that has already been loaded, matching the list by its description.  The
+    /// rows of the lesson are updated in place, so the list keeps its ID.  Learning progress is
+    /// kept separately, by word, and is not affected.  If there is no list by this name, the
+    /// lesson is loaded as a new list.
+    pub fn update_lesson(&mut self, lesson: &Lesson) -> Result<()> {
         let tx = self.conn.transaction()?;
+        update_lesson(&tx, lesson)?;
+        tx.commit()?;
 
-        // Create the lesson, getting its new ID.
-        tx.execute(
-            "INSERT INTO list (name) VALUES (:name)",
-            &[(":name", &lesson.description)],
-        )?;
-        let id = tx.last_insert_rowid();
-        println!("New ID: {}", id);
-
-        for (seq, entry) in lesson.entries.iter().enumerate() {
-            let steno = format!("{}", entry.steno);
-            // println!("entry: {} {}", entry.word, entry.steno);
-            match tx.execute(
-                "INSERT INTO lesson (word, steno, listid, seq)
-                VALUES (:word, :steno, :listid, :seq)",
+        Ok(())
+    }
+
+    /// Import a whole course, with the lessons of each chapter given in order.  Every lesson is
+    /// created or updated as with `update_lesson`, and the lists are placed into the chapters of
+    /// the course.  This is all done in a single transaction, so an error leaves the database
+    /// unchanged.  Lists that were in the course before, but no longer are, are left without a
+    /// chapter.
+    pub fn load_course(&mut self, course: &Course, lessons: &[Vec<Lesson>]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM course WHERE name = :name",
+                named_params! { ":name": &course.name },
+                |row| row.get(0),
+            )
+            .optional()?;
+        let course_id = match existing {
+            Some(id) => {
+                // The chapters are rebuilt from the manifest.
+                tx.execute(
+                    "UPDATE list SET chapter = NULL, seq = NULL
+                    WHERE chapter IN (SELECT id FROM chapter WHERE course = :course)",
+                    named_params! { ":course": id },
+                )?;
+                tx.execute(
+                    "DELETE FROM chapter WHERE course = :course",
+                    named_params! { ":course": id },
+                )?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO course (name) VALUES (:name)",
+                    named_params! { ":name": &course.name },
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        for (chapter_seq, (chapter, lessons)) in course.chapters.iter().zip(lessons).enumerate() {
+            tx.execute(
+                "INSERT INTO chapter (course, name, seq) VALUES (:course, :name, :seq)",
                 named_params! {
-                    ":word": &entry.word,
-                    ":steno": &steno,
-                    ":listid": id,
-                    ":seq": seq + 1,
+                    ":course": course_id,
+                    ":name": &chapter.name,
+                    ":seq": chapter_seq + 1,
                 },
-            ) {
-                Ok(_) => (),
-                Err(msg) => {
-                    println!("Warn: {}", msg);
-                }
+            )?;
+            let chapter_id = tx.last_insert_rowid();
+
+            for (seq, lesson) in lessons.iter().enumerate() {
+                let id = update_lesson(&tx, lesson)?;
+                tx.execute(
+                    "UPDATE list SET chapter = :chapter, seq = :seq WHERE id = :id",
+                    named_params! {
+                        ":chapter": chapter_id,
+                        ":seq": seq + 1,
+                        ":id": id,
+                    },
+                )?;
             }
         }
 
@@ -122,6 +295,186 @@ impl Db {
         Ok(())
     }
 
+    /// Find words being learned whose outline no longer matches any lesson that contains them.
+    /// This happens when a lesson is fixed and re-imported, as the learn table keeps its own copy
+    /// of the steno.  If only the alternates have changed, the lesson with the same outline is
+    /// the replacement.  Otherwise, the candidates are the outlines of that word in the lessons
+    /// that aren't already being learned.
+    pub fn get_stale(&mut self) -> Result<Vec<Stale>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT DISTINCT learn.word, learn.steno, learn.alts, lesson.steno, lesson.alts
+            FROM learn JOIN lesson USING (word)
+            WHERE NOT EXISTS (
+                    SELECT 1 FROM lesson AS l
+                    WHERE l.word = learn.word AND
+                        l.steno = learn.steno AND
+                        l.alts = learn.alts) AND
+                (lesson.steno = learn.steno OR (
+                    NOT EXISTS (
+                        SELECT 1 FROM lesson AS l
+                        WHERE l.word = learn.word AND l.steno = learn.steno) AND
+                    NOT EXISTS (
+                        SELECT 1 FROM learn AS k
+                        WHERE k.word = lesson.word AND k.steno = lesson.steno)))
+            ORDER BY learn.word, learn.steno, lesson.listid, lesson.seq",
+        )?;
+        let mut result: Vec<Stale> = vec![];
+        for row in stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (row.get(1)?, row.get(2)?),
+                (row.get(3)?, row.get(4)?),
+            ))
+        })? {
+            let (word, old, new): (String, (String, String), (String, String)) = row?;
+            match result.last_mut() {
+                Some(last) if last.word == word && last.old.0 == old.0 => {
+                    if !last.new.contains(&new) {
+                        last.new.push(new);
+                    }
+                }
+                _ => result.push(Stale {
+                    word,
+                    old,
+                    new: vec![new],
+                }),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Bring the learn table back in line with the lessons, showing each word whose outline has
+    /// changed.  Words with conflicting outlines in different lessons are only shown, as there is
+    /// no way to know which is wanted.  Returns the number of stale words found.
+    pub fn sync(&mut self, mode: SyncMode) -> Result<usize> {
+        let stale = self.get_stale()?;
+        let now = get_now();
+
+        let tx = self.conn.transaction()?;
+        for item in &stale {
+            let old = outlines(&item.old.0, &item.old.1);
+            if item.new.len() > 1 {
+                let new: Vec<_> = item.new.iter().map(|(s, a)| outlines(s, a)).collect();
+                println!("  {}: {} -> ambiguous: {}", item.word, old, new.join(", "));
+                continue;
+            }
+            let (steno, alts) = &item.new[0];
+            println!("  {}: {} -> {}", item.word, old, outlines(steno, alts));
+
+            match mode {
+                SyncMode::Show => (),
+                SyncMode::Update => {
+                    tx.execute(
+                        "UPDATE learn SET steno = :steno, alts = :alts
+                        WHERE word = :word AND steno = :old",
+                        named_params! {
+                            ":steno": steno,
+                            ":alts": alts,
+                            ":word": &item.word,
+                            ":old": &item.old.0,
+                        },
+                    )?;
+                }
+                SyncMode::Review => {
+                    tx.execute(
+                        "UPDATE learn SET steno = :steno, alts = :alts, next = :now
+                        WHERE word = :word AND steno = :old",
+                        named_params! {
+                            ":steno": steno,
+                            ":alts": alts,
+                            ":now": now,
+                            ":word": &item.word,
+                            ":old": &item.old.0,
+                        },
+                    )?;
+                }
+                SyncMode::Reset => {
+                    tx.execute(
+                        "UPDATE learn SET steno = :steno, alts = :alts,
+                            goods = 0, interval = 3.0, next = :now
+                        WHERE word = :word AND steno = :old",
+                        named_params! {
+                            ":steno": steno,
+                            ":alts": alts,
+                            ":now": now,
+                            ":word": &item.word,
+                            ":old": &item.old.0,
+                        },
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+
+        Ok(stale.len())
+    }
+
+    /// Retrieve a list from the database as a lesson, with the entries in order.
+    pub fn get_lesson(&mut self, list: usize) -> Result<Lesson> {
+        let description: String = self
+            .conn
+            .query_row(
+                "SELECT name FROM list WHERE id = :id",
+                named_params! { ":id": list },
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!("There is no list {}", list))?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT word, steno, alts, label, note, tags FROM lesson
+            WHERE listid = :listid ORDER BY seq",
+        )?;
+        let mut entries = vec![];
+        for row in stmt.query_map(named_params! { ":listid": list }, |row| {
+            let steno: String = row.get(1)?;
+            let alts: String = row.get(2)?;
+            let tags: String = row.get(5)?;
+            Ok((row.get(0)?, steno, alts, row.get(3)?, row.get(4)?, tags))
+        })? {
+            let (word, steno, alts, label, note, tags) = row?;
+            entries.push(Entry {
+                label,
+                note,
+                tags: Entry::parse_tags(&tags),
+                ..Entry::new(
+                    word,
+                    StenoPhrase::parse(&steno)?,
+                    StenoPhrase::parse_list(&alts)?,
+                )
+            });
+        }
+
+        Ok(Lesson {
+            description,
+            entries,
+            warnings: vec![],
+        })
+    }
+
+    /// Retrieve every lesson entry in the database, along with the list it is in.
+    pub fn get_lesson_entries(&mut self) -> Result<Vec<ListEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT word, steno, alts, list.id, list.name, label
+            FROM lesson JOIN list ON lesson.listid = list.id
+            ORDER BY list.id, lesson.seq",
+        )?;
+        let mut result = vec![];
+        for row in stmt.query_map([], |row| {
+            Ok(ListEntry {
+                word: row.get(0)?,
+                steno: row.get(1)?,
+                alts: row.get(2)?,
+                listid: row.get(3)?,
+                list: row.get(4)?,
+                label: row.get(5)?,
+            })
+        })? {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
     /// Show the information about lessons.
     pub fn info(&mut self, seen: bool, unseen: bool, hide_learned: bool) -> Result<()> {
         let mut stmt = self.conn.prepare(
@@ -129,18 +482,28 @@ impl Db {
             list.id,
             (SELECT COUNT(*) FROM learn, lesson WHERE
                 lesson.listid = list.id AND
-                learn.word = lesson.word),
+                learn.word = lesson.word AND
+                learn.steno = lesson.steno),
             (SELECT COUNT(*) FROM lesson WHERE lesson.listid = list.id),
-            name
+            list.name,
+            course.name,
+            chapter.name
             FROM list
-            ORDER by list.id",
+            LEFT JOIN chapter ON list.chapter = chapter.id
+            LEFT JOIN course ON chapter.course = course.id
+            ORDER by course.id, chapter.seq, list.seq, list.id",
         )?;
+        // Lists that are part of a course are shown under their course and chapter, after the
+        // ones that aren't.
+        let mut heading: (Option<String>, Option<String>) = (None, None);
         for row in stmt.query_map([], |row| {
             Ok(InfoResult {
                 id: row.get(0)?,
                 num: row.get(1)?,
                 total: row.get(2)?,
                 name: row.get(3)?,
+                course: row.get(4)?,
+                chapter: row.get(5)?,
             })
         })? {
             let row = row?;
@@ -153,8 +516,20 @@ impl Db {
             if hide_learned && row.num == row.total {
                 continue;
             }
+            if heading.0 != row.course {
+                if let Some(course) = &row.course {
+                    println!("{}", course);
+                }
+            }
+            if heading != (row.course.clone(), row.chapter.clone()) {
+                if let Some(chapter) = &row.chapter {
+                    println!("  {}", chapter);
+                }
+                heading = (row.course.clone(), row.chapter.clone());
+            }
             println!(
-                "  {:2}. {:5}/{:<5} ({:5}): {}{}",
+                "{}  {:2}. {:5}/{:<5} ({:5}): {}{}",
+                if row.chapter.is_some() { "    " } else { "" },
                 row.id,
                 row.num,
                 row.total,
@@ -174,7 +549,15 @@ impl Db {
 
         let mut stmt = self.conn.prepare(
             "
-            SELECT word, steno, goods, interval, next, factor
+            SELECT word, steno, goods, interval, next, factor, alts,
+                (SELECT label FROM lesson
+                    WHERE lesson.word = learn.word AND lesson.steno = learn.steno AND
+                        label IS NOT NULL
+                    ORDER BY listid, seq LIMIT 1),
+                (SELECT note FROM lesson
+                    WHERE lesson.word = learn.word AND lesson.steno = learn.steno AND
+                        note IS NOT NULL
+                    ORDER BY listid, seq LIMIT 1)
             FROM learn
             WHERE next < :now
             ORDER BY interval, next
@@ -187,9 +570,13 @@ impl Db {
             },
             |row| {
                 let steno: String = row.get(1)?;
+                let alts: String = row.get(6)?;
                 Ok(Work {
                     text: row.get(0)?,
                     strokes: StenoPhrase::parse(&steno).unwrap(),
+                    alternates: StenoPhrase::parse_list(&alts).unwrap(),
+                    label: row.get(7)?,
+                    note: row.get(8)?,
                     goods: row.get(2)?,
                     interval: row.get(3)?,
                     next: row.get(4)?,
@@ -250,24 +637,33 @@ impl Db {
         tx.execute("CREATE TEMP TABLE minmax AS
             SELECT listid, MIN(seq) AS seqmin, MAX(seq) AS seqmax
             FROM lesson
-            WHERE lesson.word NOT IN (SELECT word FROM learn)
+            WHERE NOT EXISTS (SELECT 1 FROM learn
+                WHERE learn.word = lesson.word AND learn.steno = lesson.steno)
             GROUP BY listid", [])?;
 
         let mut stmt = tx.prepare(
             "SELECT word, steno,
                 seqmax - seq + 1,
-                lesson.listid
+                lesson.listid,
+                alts,
+                label,
+                note
             FROM lesson, minmax
             WHERE lesson.listid IN finder AND
                 lesson.listid = minmax.listid AND
-                lesson.word NOT IN (SELECT word FROM learn)
+                NOT EXISTS (SELECT 1 FROM learn
+                    WHERE learn.word = lesson.word AND learn.steno = lesson.steno)
             GROUP BY lesson.listid
             ORDER BY seq")?;
         let works: Vec<_> = stmt.query_map([], |row| {
             let steno: String = row.get(1)?;
+            let alts: String = row.get(4)?;
             Ok(Minmax {
                 word: row.get(0)?,
                 steno: StenoPhrase::parse(&steno).unwrap(),
+                alternates: StenoPhrase::parse_list(&alts).unwrap(),
+                label: row.get(5)?,
+                note: row.get(6)?,
                 progress: row.get(2)?,
                 listid: row.get(3)?,
             })})?.collect();
@@ -299,6 +695,9 @@ impl Db {
                 return Ok(Some(Work {
                     text: w.word,
                     strokes: w.steno,
+                    alternates: w.alternates,
+                    label: w.label,
+                    note: w.note,
                     goods: 0,
                     interval: 3.0,
                     next: 0.0,
@@ -324,9 +723,12 @@ impl Db {
                     goods,
                     interval,
                     next,
-                    factor
+                    factor,
+                    learn.alts,
+                    lesson.label,
+                    lesson.note
             FROM
-                    lesson LEFT JOIN learn USING (word)
+                    lesson LEFT JOIN learn USING (word, steno)
             WHERE
                     lesson.listid = :list AND
                     seq >= :start
@@ -347,9 +749,13 @@ impl Db {
                     None => return Ok(Err(anyhow!("Not all words in lesson have been learned"))),
                 };
                 let steno: String = row.get(1)?;
+                let alts: String = row.get(6)?;
                 Ok(Ok(Work {
-                    text: text,
+                    text,
                     strokes: StenoPhrase::parse(&steno).unwrap(),
+                    alternates: StenoPhrase::parse_list(&alts).unwrap(),
+                    label: row.get(7)?,
+                    note: row.get(8)?,
                     goods: row.get(2)?,
                     interval: row.get(3)?,
                     next: row.get(4)?,
@@ -359,7 +765,7 @@ impl Db {
         }
 
         let result: Result<Vec<_>> = result.into_iter().collect();
-        Ok(result?)
+        result
     }
 
     /// Update the given work in the database.  `corrections` is the number of corrections the user
@@ -410,17 +816,30 @@ impl Db {
         } else {
             (work.interval / 4.0).max(5.0)
         };
+        self.store(work, goods, interval, factor)
+    }
+
+    /// Reschedule the given work at its current interval, without counting it as either good or
+    /// an error.  Used when an accepted, but not preferred, outline was written.
+    pub fn keep(&mut self, work: &Work) -> Result<()> {
+        self.store(work, work.goods, work.interval, work.factor)
+    }
+
+    // Write the learning state of a word, due after `interval` from now.
+    fn store(&mut self, work: &Work, goods: usize, interval: f64, factor: f64) -> Result<()> {
         let next = get_now() + interval;
         let steno = format!("{}", work.strokes);
+        let alts = StenoPhrase::join_list(&work.alternates);
 
         let tx = self.conn.transaction()?;
         tx.execute(
             "
             INSERT OR REPLACE INTO learn
-            (word, steno, goods, interval, next, factor)
-            VALUES (:word, :steno, :goods, :interval, :next, :factor)",
+            (word, steno, alts, goods, interval, next, factor)
+            VALUES (:word, :steno, :alts, :goods, :interval, :next, :factor)",
             named_params! {
                 ":steno": &steno,
+                ":alts": &alts,
                 ":goods": goods,
                 ":interval": interval,
                 ":next": next,
@@ -437,10 +856,11 @@ impl Db {
     pub fn record_error(&mut self, work: &Work, actual: &str) -> Result<()> {
         let tx = self.conn.transaction()?;
         tx.execute(
-            "INSERT INTO errors (stamp, word, goods, interval, next, actual)
-                VALUES (datetime(), :word, :goods, :interval, :next, :actual)",
+            "INSERT INTO errors (stamp, word, steno, goods, interval, next, actual)
+                VALUES (datetime(), :word, :steno, :goods, :interval, :next, :actual)",
             named_params! {
                 ":word": &work.text,
+                ":steno": &work.strokes.to_string(),
                 ":goods": work.goods,
                 ":interval": work.interval,
                 ":next": work.next,
@@ -450,6 +870,79 @@ impl Db {
         Ok(())
     }
 
+    /// Record how long a word took to write: `first` is the time from when it was shown to its
+    /// first stroke, and `gap` the longest time between its strokes.
+    pub fn record_latency(
+        &mut self,
+        work: &Work,
+        first: f64,
+        gaps: &[f64],
+        corrections: usize,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO latency (stamp, word, steno, first, gaps, corrections)
+                VALUES (datetime(), :word, :steno, :first, :gaps, :corrections)",
+            named_params! {
+                ":word": &work.text,
+                ":steno": &work.strokes.to_string(),
+                ":first": first,
+                ":gaps": serde_json::to_string(gaps)?,
+                ":corrections": corrections,
+            })?;
+        Ok(())
+    }
+
+    /// Retrieve the times taken to write words without corrections in the last `days` days.
+    pub fn get_latencies(&mut self, days: f64) -> Result<Vec<LatencyEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT word, steno, first, gaps FROM latency
+                WHERE corrections = 0 AND stamp >= datetime('now', :since)")?;
+        let since = format!("-{} days", days);
+        let mut result = vec![];
+        for row in stmt.query_map(named_params! { ":since": since }, |row| {
+            let gaps: String = row.get(3)?;
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, gaps))
+        })? {
+            let (word, steno, first, gaps) = row?;
+            result.push(LatencyEntry {
+                word,
+                steno,
+                first,
+                gaps: serde_json::from_str(&gaps)?,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Retrieve the errors recorded in the last `days` days, or all of them, oldest first.
+    pub fn get_errors(&mut self, days: Option<f64>) -> Result<Vec<ErrorEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT steno, actual FROM errors
+                WHERE :since IS NULL OR stamp >= datetime('now', :since)
+                ORDER BY stamp")?;
+        let since = days.map(|days| format!("-{} days", days));
+        let mut result = vec![];
+        for row in stmt.query_map(named_params! { ":since": since }, |row| {
+            Ok(ErrorEntry {
+                steno: row.get(0)?,
+                actual: row.get(1)?,
+            })
+        })? {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
+    /// Retrieve the outlines that have been written correctly, with the number of times.
+    pub fn get_learned(&mut self) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self.conn.prepare("SELECT steno, goods FROM learn WHERE goods > 0")?;
+        let mut result = vec![];
+        for row in stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))? {
+            result.push(row?);
+        }
+        Ok(result)
+    }
+
     /// Retrieve a histogram of the number of words in range of dates.
     pub fn get_histogram(&mut self) -> Result<Vec<Bucket>> {
         let mut result: Vec<_> = BUCKETS
@@ -582,13 +1075,187 @@ impl Db {
 
     pub fn get_minutes_practiced(&mut self) -> Result<f64> {
         Ok(self.conn.query_row("
-            SELECT SUM(24 * 60 * (julianday(stop) - julianday(start)))
+            SELECT COALESCE(SUM(24 * 60 * (julianday(stop) - julianday(start))), 0.0)
             FROM history
             WHERE stop IS NOT NULL", [],
             |row| row.get(0))?)
     }
 }
 
+// Find the ID of the list with the given name.
+fn find_list(conn: &Connection, name: &str) -> Result<Option<usize>> {
+    Ok(conn
+        .query_row(
+            "SELECT id FROM list WHERE name = :name",
+            named_params! { ":name": name },
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+// Create a new list holding the lesson, returning its ID.
+fn insert_lesson(tx: &Connection, lesson: &Lesson) -> Result<usize> {
+    // Create the lesson, getting its new ID.
+    tx.execute(
+        "INSERT INTO list (name) VALUES (:name)",
+        &[(":name", &lesson.description)],
+    )?;
+    let id = tx.last_insert_rowid() as usize;
+    println!("New ID: {}", id);
+
+    for (seq, entry) in lesson.entries.iter().enumerate() {
+        let steno = format!("{}", entry.steno);
+        let alts = StenoPhrase::join_list(&entry.alternates);
+        // println!("entry: {} {}", entry.word, entry.steno);
+        match tx.execute(
+            "INSERT INTO lesson (word, steno, alts, label, note, tags, listid, seq)
+            VALUES (:word, :steno, :alts, :label, :note, :tags, :listid, :seq)",
+            named_params! {
+                ":word": &entry.word,
+                ":steno": &steno,
+                ":alts": &alts,
+                ":label": &entry.label,
+                ":note": &entry.note,
+                ":tags": entry.tags.join(","),
+                ":listid": id,
+                ":seq": seq + 1,
+            },
+        ) {
+            Ok(_) => (),
+            Err(msg) => {
+                println!("Warn: {}", msg);
+            }
+        }
+    }
+
+    Ok(id)
+}
+
+// Update the list with the lesson's name in place, or create it if there isn't one, returning
+// the list's ID.
+fn update_lesson(tx: &Connection, lesson: &Lesson) -> Result<usize> {
+    let id = match find_list(tx, &lesson.description)? {
+        Some(id) => id,
+        None => return insert_lesson(tx, lesson),
+    };
+    println!("Updating ID: {}", id);
+
+    // Gather the old entries, to be able to report what changed.
+    // A word can appear more than once, with different outlines.
+    let mut old: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT word, steno, alts, seq FROM lesson WHERE listid = :listid
+            ORDER BY seq",
+        )?;
+        for row in stmt.query_map(named_params! { ":listid": id }, |row| {
+            let steno: String = row.get(1)?;
+            let alts: String = row.get(2)?;
+            Ok((row.get(0)?, (outlines(&steno, &alts), row.get(3)?)))
+        })? {
+            let (word, info) = row?;
+            old.entry(word).or_default().push(info);
+        }
+    }
+
+    // Match the entries that are unchanged first, so that a word with several outlines
+    // pairs up the right ones.  What is left over of each word has changed.
+    let mut pending = vec![];
+    let mut moved = 0;
+    for (seq, entry) in lesson.entries.iter().enumerate() {
+        let new_steno = outlines(
+            &entry.steno.to_string(),
+            &StenoPhrase::join_list(&entry.alternates),
+        );
+        let olds = old.entry(entry.word.clone()).or_default();
+        match olds.iter().position(|(steno, _)| *steno == new_steno) {
+            Some(pos) => {
+                if olds.remove(pos).1 != seq + 1 {
+                    moved += 1;
+                }
+            }
+            None => pending.push((entry, new_steno)),
+        }
+    }
+    let mut added = 0;
+    let mut changed = 0;
+    for (entry, new_steno) in pending {
+        let olds = old.entry(entry.word.clone()).or_default();
+        if olds.is_empty() {
+            added += 1;
+        } else {
+            let (old_steno, _) = olds.remove(0);
+            println!("  {}: {} -> {}", entry.word, old_steno, new_steno);
+            changed += 1;
+        }
+    }
+    let removed: usize = old.values().map(|v| v.len()).sum();
+
+    for (seq, entry) in lesson.entries.iter().enumerate() {
+        let seq = seq + 1;
+        let steno = format!("{}", entry.steno);
+        let alts = StenoPhrase::join_list(&entry.alternates);
+
+        // Replace whatever is at this position, adding a new row if the list has grown.
+        let count = tx.execute(
+            "UPDATE lesson SET word = :word, steno = :steno, alts = :alts,
+                label = :label, note = :note, tags = :tags
+            WHERE listid = :listid AND seq = :seq",
+            named_params! {
+                ":word": &entry.word,
+                ":steno": &steno,
+                ":alts": &alts,
+                ":label": &entry.label,
+                ":note": &entry.note,
+                ":tags": entry.tags.join(","),
+                ":listid": id,
+                ":seq": seq,
+            },
+        )?;
+        if count == 0 {
+            tx.execute(
+                "INSERT INTO lesson (word, steno, alts, label, note, tags, listid, seq)
+                VALUES (:word, :steno, :alts, :label, :note, :tags, :listid, :seq)",
+                named_params! {
+                    ":word": &entry.word,
+                    ":steno": &steno,
+                    ":alts": &alts,
+                    ":label": &entry.label,
+                    ":note": &entry.note,
+                    ":tags": entry.tags.join(","),
+                    ":listid": id,
+                    ":seq": seq,
+                },
+            )?;
+        }
+    }
+
+    // Anything past the end of the new lesson is gone.
+    tx.execute(
+        "DELETE FROM lesson WHERE listid = :listid AND seq > :len",
+        named_params! {
+            ":listid": id,
+            ":len": lesson.entries.len(),
+        },
+    )?;
+
+    println!(
+        "  {} added, {} changed, {} moved, {} removed",
+        added, changed, moved, removed
+    );
+
+    Ok(id)
+}
+
+// Format an outline along with its alternates, as it would be written in a lesson.
+fn outlines(steno: &str, alts: &str) -> String {
+    if alts.is_empty() {
+        steno.to_string()
+    } else {
+        format!("{} | {}", steno, alts)
+    }
+}
+
 /// Steno can be made as "Work" which is a linear sequence of strokes, and pieces of text that go
 /// with each stroke.  For multiple stroke words, only the last stroke will include the text.  This
 /// is similar to real behavior, but without the false words showing up first and then being
@@ -597,6 +1264,11 @@ impl Db {
 pub struct Work {
     pub text: String,
     pub strokes: StenoPhrase,
+    /// Other outlines that are also accepted.
+    pub alternates: Vec<StenoPhrase>,
+    /// The label and note from the lesson the word is in.
+    pub label: Option<String>,
+    pub note: Option<String>,
     pub goods: usize,
     pub interval: f64,
     pub next: f64,
@@ -604,11 +1276,82 @@ pub struct Work {
     // pub items: Vec<WorkItem>,
 }
 
+impl Work {
+    /// The text to show for this word, which is the label if there is one.
+    pub fn display(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.text)
+    }
+}
+
+/// An entry of a lesson in the database.
+#[derive(Debug)]
+pub struct ListEntry {
+    pub word: String,
+    pub steno: String,
+    pub alts: String,
+    pub listid: usize,
+    pub list: String,
+    pub label: Option<String>,
+}
+
+/// A recorded error.
+#[derive(Debug)]
+pub struct ErrorEntry {
+    /// The outline that should have been written.
+    pub steno: String,
+    /// All of the strokes that were written, including the corrections.
+    pub actual: String,
+}
+
+/// The time taken to write a word.
+#[derive(Debug)]
+pub struct LatencyEntry {
+    pub word: String,
+    pub steno: String,
+    /// Seconds from when the word was shown to its first stroke.
+    pub first: f64,
+    /// The time between each of its strokes, in order.
+    pub gaps: Vec<f64>,
+}
+
+impl LatencyEntry {
+    /// The longest time between its strokes, for words of more than one stroke.
+    pub fn longest_gap(&self) -> Option<f64> {
+        self.gaps.iter().copied().reduce(f64::max)
+    }
+}
+
+/// A word being learned whose outline doesn't match the lessons.
+#[derive(Debug)]
+pub struct Stale {
+    pub word: String,
+    /// The outline, and alternates, in the learn table.
+    pub old: (String, String),
+    /// The outlines, and alternates, found in the lessons.
+    pub new: Vec<(String, String)>,
+}
+
+/// What to do with words whose outline has changed in the lessons.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SyncMode {
+    /// Only show the differences.
+    Show,
+    /// Update the outline, keeping the learning progress.
+    Update,
+    /// Update the outline, and make the word due now, so it will be reviewed right away.
+    Review,
+    /// Update the outline, and reset the word so that it will be learned again.
+    Reset,
+}
+
 /// Query results for getting work to do.
 #[derive(Debug)]
 struct Minmax {
     word: String,
     steno: StenoPhrase,
+    alternates: Vec<StenoPhrase>,
+    label: Option<String>,
+    note: Option<String>,
     progress: f64,
     listid: usize,
 }
@@ -636,6 +1379,8 @@ struct InfoResult {
     num: usize,
     total: usize,
     name: String,
+    course: Option<String>,
+    chapter: Option<String>,
 }
 
 /// Buckets describing a histogram result.
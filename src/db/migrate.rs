@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Schema migrations.
+//!
+//! Each step of the migration chain is one of the files from the `migrations` directory, embedded
+//! in the binary.  The steps are named by the schema version they upgrade from and to, and each
+//! one ends by updating the version in the schema table.  Every step is run in its own
+//! transaction, so a failure leaves the database at the last version that was reached.
+
+use super::SCHEMA_VERSION;
+use anyhow::{bail, Result};
+use rusqlite::{Connection, DatabaseName};
+use std::path::{Path, PathBuf};
+
+/// A single step in upgrading the schema.
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub sql: &'static str,
+}
+
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: "2022-03-02a",
+        to: "2022-03-06a",
+        sql: include_str!("../../migrations/2022-03-02a-2022-03-06a.sql"),
+    },
+    Migration {
+        from: "2022-03-06a",
+        to: "2022-03-12a",
+        sql: include_str!("../../migrations/2022-03-06a-2022-03-12a.sql"),
+    },
+    Migration {
+        from: "2022-03-12a",
+        to: "2022-04-14a",
+        sql: include_str!("../../migrations/2022-03-12a-2022-04-14a.sql"),
+    },
+    Migration {
+        from: "2022-04-14a",
+        to: "2023-11-10a",
+        sql: include_str!("../../migrations/2022-04-14a-2023-11-10a.sql"),
+    },
+    Migration {
+        from: "2023-11-10a",
+        to: "2026-10-16a",
+        sql: include_str!("../../migrations/2023-11-10a-2026-10-16a.sql"),
+    },
+    Migration {
+        from: "2026-10-16a",
+        to: "2026-10-16b",
+        sql: include_str!("../../migrations/2026-10-16a-2026-10-16b.sql"),
+    },
+    Migration {
+        from: "2026-10-16b",
+        to: "2026-10-16c",
+        sql: include_str!("../../migrations/2026-10-16b-2026-10-16c.sql"),
+    },
+    Migration {
+        from: "2026-10-16c",
+        to: "2026-10-16d",
+        sql: include_str!("../../migrations/2026-10-16c-2026-10-16d.sql"),
+    },
+    Migration {
+        from: "2026-10-16d",
+        to: "2026-10-16e",
+        sql: include_str!("../../migrations/2026-10-16d-2026-10-16e.sql"),
+    },
+    Migration {
+        from: "2026-10-16e",
+        to: "2026-10-16f",
+        sql: include_str!("../../migrations/2026-10-16e-2026-10-16f.sql"),
+    },
+];
+
+/// Read the schema version of the database.
+pub fn get_version(conn: &Connection) -> Result<String> {
+    Ok(conn.query_row("SELECT version FROM schema", [], |row| row.get(0))?)
+}
+
+/// Determine the steps needed to bring a database at `version` up to the current schema.
+pub fn plan(version: &str) -> Result<Vec<&'static Migration>> {
+    let mut result = vec![];
+    let mut version = version;
+    while version != SCHEMA_VERSION {
+        match MIGRATIONS.iter().find(|m| m.from == version) {
+            Some(step) => {
+                result.push(step);
+                version = step.to;
+            }
+            None => bail!(
+                "Schema version {} has no upgrade path to {}",
+                version,
+                SCHEMA_VERSION
+            ),
+        }
+    }
+    Ok(result)
+}
+
+/// Save a copy of the database before it is upgraded.  The copy is placed next to the database,
+/// named after the version it was at.  Returns the name of the copy.
+pub fn backup(conn: &Connection, path: &Path, version: &str) -> Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.bak", version));
+    let dest = PathBuf::from(name);
+    conn.backup(DatabaseName::Main, &dest, None)?;
+    Ok(dest)
+}
+
+/// Apply the given steps, each within its own transaction.
+pub fn apply(conn: &mut Connection, steps: &[&Migration]) -> Result<()> {
+    for step in steps {
+        let tx = conn.transaction()?;
+        tx.execute_batch(&statements(step.sql))?;
+        let version = get_version(&tx)?;
+        if version != step.to {
+            bail!(
+                "Migration from {} left schema at {}, expecting {}",
+                step.from,
+                version,
+                step.to
+            );
+        }
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// Some of the older steps were written to be applied by hand, and so have their own BEGIN and
+// COMMIT.  These are left out, as each step is already run in a transaction.
+fn statements(sql: &str) -> String {
+    sql.lines()
+        .filter(|line| !matches!(line.trim(), "BEGIN;" | "COMMIT;"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn migrate_oldest() {
+    // The schema as it was before any migrations were written.
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE learn (
+            word TEXT UNIQUE PRIMARY KEY,
+            steno TEXT NOT NULL,
+            goods INTEGER NOT NULL,
+            interval REAL NOT NULL,
+            next REAL NOT NULL);
+        CREATE INDEX learn_steno_idx ON learn (steno);
+        CREATE INDEX learn_next_idx ON learn (next);
+        CREATE TABLE list (
+            id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE NOT NULL);
+        CREATE TABLE lesson (
+            word TEXT NOT NULL,
+            steno TEXT NOT NULL,
+            listid INTEGER REFERENCES list (id) NOT NULL,
+            seq INTEGER NOT NULL,
+            UNIQUE (word, listid));
+        CREATE TABLE schema (version TEXT NOT NULL);
+        INSERT INTO schema VALUES ('2022-03-02a');
+        INSERT INTO list VALUES (1, 'basics');
+        INSERT INTO lesson VALUES ('cat', 'KAT', 1, 1);
+        INSERT INTO learn VALUES ('cat', 'KAT', 3, 100.0, 0.0);",
+    )
+    .unwrap();
+
+    let steps = plan("2022-03-02a").unwrap();
+    assert_eq!(steps.len(), MIGRATIONS.len());
+    apply(&mut conn, &steps).unwrap();
+    assert_eq!(get_version(&conn).unwrap(), SCHEMA_VERSION);
+
+    let (steno, factor, alts): (String, f64, String) = conn
+        .query_row(
+            "SELECT steno, factor, alts FROM learn WHERE word = 'cat'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(steno, "KAT");
+    assert_eq!(factor, 4.0);
+    assert_eq!(alts, "");
+
+    assert!(plan(SCHEMA_VERSION).unwrap().is_empty());
+    assert!(plan("1999-01-01a").is_err());
+}
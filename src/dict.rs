@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Plover dictionaries.
+//!
+//! Plover's dictionaries have to be turned off while drilling, so that the strokes themselves are
+//! seen.  To still show what the strokes would have written, dictionaries can be loaded here, and
+//! used to translate the strokes.  Only the simplest part of what Plover does is done: strokes
+//! are matched to the longest outline in the dictionaries, and the translations are shown as
+//! they are written in the dictionary, without interpreting any formatting.
+
+use crate::stroke::{StenoWord, Stroke};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    entries: HashMap<Vec<Stroke>, String>,
+    // The most strokes in any outline.
+    longest: usize,
+}
+
+impl Dictionary {
+    /// Load the dictionaries at the given paths.  As with Plover, when an outline is in more than
+    /// one dictionary, the first one given is used.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Dictionary> {
+        let mut dict = Dictionary::default();
+        for path in paths {
+            let path = path.as_ref();
+            let text = fs::read_to_string(path)?;
+            dict.add(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        }
+        Ok(dict)
+    }
+
+    // Add the entries from the text of a dictionary, keeping any that are already present.
+    // Outlines that can't be read, such as those for other steno systems, are skipped.
+    fn add(&mut self, text: &str) -> Result<()> {
+        let dict: serde_json::Map<String, serde_json::Value> = serde_json::from_str(text)?;
+        for (steno, word) in dict {
+            let (steno, word) = match (StenoWord::parse(&steno), word) {
+                (Ok(steno), serde_json::Value::String(word)) => (steno.0, word),
+                _ => continue,
+            };
+            self.longest = self.longest.max(steno.len());
+            self.entries.entry(steno).or_insert(word);
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Translate the strokes, taking the longest outline that matches at each point.  Strokes
+    /// that aren't in the dictionary are given as the steno itself, as Plover does.
+    pub fn translate(&self, strokes: &[Stroke]) -> Vec<String> {
+        let mut result = vec![];
+        let mut pos = 0;
+        while pos < strokes.len() {
+            let longest = self.longest.min(strokes.len() - pos);
+            let found = (1..=longest)
+                .rev()
+                .find_map(|len| Some((len, self.entries.get(&strokes[pos..pos + len])?)));
+            match found {
+                Some((len, word)) => {
+                    result.push(word.clone());
+                    pos += len;
+                }
+                None => {
+                    result.push(strokes[pos].to_string());
+                    pos += 1;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[test]
+fn longest_match() {
+    let mut dict = Dictionary::default();
+    dict.add(r#"{"KAT": "cat", "KAP": "cap", "KAT/HROG": "catalog", "-S": "{^s}"}"#)
+        .unwrap();
+    dict.add(r#"{"KAT": "kat", "HROG": "log"}"#)
+        .unwrap();
+
+    let strokes = StenoWord::parse("KAT/HROG/KAT/-S/TKOG").unwrap().0;
+    assert_eq!(dict.translate(&strokes), ["catalog", "cat", "{^s}", "TKOG"]);
+    assert_eq!(dict.translate(&strokes[1..]), ["log", "cat", "{^s}", "TKOG"]);
+    assert!(dict.add("[]").is_err());
+}
@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Analysis of the errors made while learning.
+//!
+//! Each error records the outline that should have been written, and every stroke that was
+//! written, including the undo strokes used to correct it.  These are replayed to find each
+//! attempt that was corrected, which is then compared with the outline:
+//!
+//! - A key that should have been pressed, but wasn't, is missed.  One that was pressed, but
+//!   shouldn't have been, is extra.
+//! - A missed key with an extra key next to it on the keyboard is a swap, likely the right finger
+//!   on the wrong key, or the wrong finger.
+//! - The right keys, but divided between the strokes differently, is a word-boundary split.
+//! - Writing more strokes than the outline has is a wrong stroke count.
+//!
+//! Along with the words written correctly, the errors also give the accuracy of each key: how
+//! often it is missed when it should be pressed, and how often it is pressed when it shouldn't be.
+
+use crate::db::{Db, ErrorEntry};
+use crate::stroke::{system, StenoPhrase, StenoWord, Stroke};
+use anyhow::Result;
+use crossterm::style::{style, Color, Stylize};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+
+/// A single mistake found in an attempt.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mistake {
+    Missed(Stroke),
+    Extra(Stroke),
+    /// A key pressed in place of its neighbour.
+    Swapped { expected: Stroke, written: Stroke },
+    /// More strokes written than the outline has.
+    StrokeCount { expected: usize, written: usize },
+    /// The right keys, divided between the strokes differently.
+    Split,
+}
+
+/// Where the keys are on the keyboard, to find the keys that are next to each other.
+pub struct Keyboard(Vec<(Stroke, usize, usize)>);
+
+impl Keyboard {
+    /// The keyboard of the current system, as laid out in its diagram.
+    pub fn new() -> Keyboard {
+        let mut places = vec![];
+        for (y, row) in system::current().diagram().into_iter().enumerate() {
+            for (x, (_, key)) in row.into_iter().enumerate() {
+                if let Some(key) = key {
+                    places.push((key, y, x));
+                }
+            }
+        }
+        Keyboard(places)
+    }
+
+    // Keys are neighbours when they are beside, above or below each other in the diagram,
+    // allowing for the borders drawn between the keys.
+    fn neighbours(&self, a: Stroke, b: Stroke) -> bool {
+        let places = |key| {
+            self.0
+                .iter()
+                .filter(move |(k, _, _)| *k == key)
+                .map(|&(_, y, x)| (y, x))
+        };
+        places(a).any(|(ay, ax)| {
+            places(b).any(|(by, bx)| ay.abs_diff(by) <= 1 && ax.abs_diff(bx) <= 2)
+        })
+    }
+}
+
+/// Replay the strokes that were written, returning what had been written each time a correction
+/// was started with an undo stroke.
+pub fn attempts(written: &[Stroke]) -> Vec<Vec<Stroke>> {
+    let mut result = vec![];
+    let mut sofar = vec![];
+    let mut undoing = false;
+    for &stroke in written {
+        if stroke.is_star() {
+            if !undoing && !sofar.is_empty() {
+                result.push(sofar.clone());
+            }
+            undoing = true;
+            sofar.pop();
+        } else {
+            undoing = false;
+            sofar.push(stroke);
+        }
+    }
+    result
+}
+
+/// Find the mistakes in an attempt to write the `expected` outline.  Strokes that match the
+/// start of the outline aren't mistakes, even if they were undone.
+pub fn classify(expected: &[Stroke], written: &[Stroke], keyboard: &Keyboard) -> Vec<Mistake> {
+    let start = expected.iter().zip(written).take_while(|(e, w)| e == w).count();
+    let (expected_rest, written_rest) = (&expected[start..], &written[start..]);
+    if written_rest.is_empty() {
+        return vec![];
+    }
+
+    let union = |strokes: &[Stroke]| strokes.iter().fold(Stroke::default(), |a, &b| a.union(b));
+    if written_rest.len() != expected_rest.len()
+        && !expected_rest.is_empty()
+        && union(written_rest) == union(expected_rest)
+    {
+        return vec![Mistake::Split];
+    }
+
+    let mut result = vec![];
+    for (&e, &w) in expected_rest.iter().zip(written_rest) {
+        let mut extra: Vec<_> = w.without(e).keys().collect();
+        for missed in e.without(w).keys() {
+            match extra.iter().position(|&x| keyboard.neighbours(missed, x)) {
+                Some(pos) => result.push(Mistake::Swapped {
+                    expected: missed,
+                    written: extra.remove(pos),
+                }),
+                None => result.push(Mistake::Missed(missed)),
+            }
+        }
+        result.extend(extra.into_iter().map(Mistake::Extra));
+    }
+    if written.len() > expected.len() {
+        result.push(Mistake::StrokeCount {
+            expected: expected.len(),
+            written: written.len(),
+        });
+    }
+    result
+}
+
+/// The mistakes found in a set of errors.  Swapped keys are counted as both missed and extra, as
+/// well as being counted as a pair.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of errors, and of attempts that were corrected.
+    pub errors: usize,
+    pub attempts: usize,
+    /// Errors whose strokes couldn't be read, such as those written with a different system.
+    pub unreadable: usize,
+    pub missed: HashMap<Stroke, usize>,
+    pub extra: HashMap<Stroke, usize>,
+    /// Swapped keys, as the key expected and the key written.
+    pub swaps: HashMap<(Stroke, Stroke), usize>,
+    pub stroke_counts: usize,
+    pub splits: usize,
+    /// The strokes that should have been written, in each attempt and in the correct write at the
+    /// end, and how many of those have each key.
+    pub strokes: usize,
+    pub expected: HashMap<Stroke, usize>,
+}
+
+impl Report {
+    /// Analyze the given errors.
+    pub fn new(entries: &[ErrorEntry]) -> Report {
+        let keyboard = Keyboard::new();
+        let mut report = Report::default();
+        for entry in entries {
+            let (expected, written) = match (
+                StenoPhrase::parse(&entry.steno),
+                StenoWord::parse(&entry.actual),
+            ) {
+                (Ok(expected), Ok(written)) => (expected.linear(), written.0),
+                _ => {
+                    report.unreadable += 1;
+                    continue;
+                }
+            };
+            report.errors += 1;
+            let attempts = attempts(&written);
+            for attempt in &attempts {
+                report.attempts += 1;
+                for mistake in classify(&expected, attempt, &keyboard) {
+                    report.add(mistake);
+                }
+            }
+            report.add_outline(&expected, attempts.len() + 1);
+        }
+        report
+    }
+
+    // Count an outline that was meant to be written `times` times.
+    fn add_outline(&mut self, outline: &[Stroke], times: usize) {
+        self.strokes += outline.len() * times;
+        for key in outline.iter().flat_map(|stroke| stroke.keys()) {
+            *self.expected.entry(key).or_default() += times;
+        }
+    }
+
+    fn add(&mut self, mistake: Mistake) {
+        match mistake {
+            Mistake::Missed(key) => *self.missed.entry(key).or_default() += 1,
+            Mistake::Extra(key) => *self.extra.entry(key).or_default() += 1,
+            Mistake::Swapped { expected, written } => {
+                *self.missed.entry(expected).or_default() += 1;
+                *self.extra.entry(written).or_default() += 1;
+                *self.swaps.entry((expected, written)).or_default() += 1;
+            }
+            Mistake::StrokeCount { .. } => self.stroke_counts += 1,
+            Mistake::Split => self.splits += 1,
+        }
+    }
+}
+
+/// How accurately each key is written.
+pub struct Accuracy(Report);
+
+impl Accuracy {
+    /// Combine the errors with the outlines learned, and the number of times each has been
+    /// written correctly.
+    pub fn new(mut report: Report, learned: &[(String, usize)]) -> Accuracy {
+        for (steno, goods) in learned {
+            if let Ok(outline) = StenoPhrase::parse(steno) {
+                report.add_outline(&outline.linear(), *goods);
+            }
+        }
+        Accuracy(report)
+    }
+
+    /// The accuracy of all of the words written.
+    pub fn load(db: &mut Db) -> Result<Accuracy> {
+        let report = Report::new(&db.get_errors(None)?);
+        Ok(Accuracy::new(report, &db.get_learned()?))
+    }
+
+    /// How often the key is missed, when it should be pressed.
+    pub fn missed(&self, key: Stroke) -> Option<f64> {
+        let count = self.0.expected.get(&key).copied().unwrap_or(0);
+        rate(self.0.missed.get(&key), count)
+    }
+
+    /// How often the key is pressed, when it shouldn't be.
+    pub fn extra(&self, key: Stroke) -> Option<f64> {
+        let count = self.0.strokes - self.0.expected.get(&key).copied().unwrap_or(0);
+        rate(self.0.extra.get(&key), count)
+    }
+
+    /// The keys with the most errors, with how often they are missed and pressed by mistake.
+    pub fn weakest(&self, top: usize) -> Vec<(Stroke, f64, f64)> {
+        let mut keys: Vec<_> = self.0.missed.keys().chain(self.0.extra.keys()).copied().collect();
+        keys.sort_by(|a, b| b.cmp(a));
+        keys.dedup();
+        let mut list: Vec<_> = keys
+            .into_iter()
+            .map(|key| {
+                let missed = self.missed(key).unwrap_or(0.0);
+                let extra = self.extra(key).unwrap_or(0.0);
+                (key, missed, extra)
+            })
+            .collect();
+        list.sort_by(|a, b| (b.1 + b.2).total_cmp(&(a.1 + a.2)));
+        list.truncate(top);
+        list
+    }
+}
+
+fn rate(errors: Option<&usize>, count: usize) -> Option<f64> {
+    if count == 0 {
+        None
+    } else {
+        Some(errors.copied().unwrap_or(0) as f64 / count as f64)
+    }
+}
+
+/// How a key is shaded in a heatmap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Shade {
+    /// Never written, so nothing is known.
+    Unknown,
+    Good,
+    Fair,
+    Weak,
+    Poor,
+}
+
+impl Shade {
+    /// The shade for a rate of errors.
+    pub fn new(rate: Option<f64>) -> Shade {
+        match rate {
+            None => Shade::Unknown,
+            Some(rate) if rate < 0.02 => Shade::Good,
+            Some(rate) if rate < 0.05 => Shade::Fair,
+            Some(rate) if rate < 0.10 => Shade::Weak,
+            Some(_) => Shade::Poor,
+        }
+    }
+}
+
+/// A description of the shading, for showing along with heatmaps.
+pub static SHADES: &str = "green under 2%, yellow under 5%, light red under 10%, red above";
+
+/// Print the accuracy of each key as heatmaps of the keyboard, when writing to a terminal, and as
+/// a list of the weakest keys.
+pub fn show_accuracy(accuracy: &Accuracy, top: usize) {
+    if std::io::stdout().is_terminal() {
+        println!("Keys missed, and pressed by mistake ({}):", SHADES);
+        let missed = heatmap(|key| accuracy.missed(key));
+        let extra = heatmap(|key| accuracy.extra(key));
+        for (a, b) in missed.iter().zip(&extra) {
+            println!("  {}   {}", a, b);
+        }
+        println!();
+    }
+
+    println!("Weakest keys:  missed  extra");
+    for (key, missed, extra) in accuracy.weakest(top) {
+        println!("  {:>4}       {:>5.1}% {:>5.1}%", key.to_string(), missed * 100.0, extra * 100.0);
+    }
+}
+
+// The lines of a diagram of the keyboard with each key shaded by its rate of errors.  Lines are
+// padded to the same width, so they can be shown side by side.
+fn heatmap(rate: impl Fn(Stroke) -> Option<f64>) -> Vec<String> {
+    let rows = system::current().diagram();
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|row| {
+            let mut line = String::new();
+            for &(ch, key) in row {
+                let text = match key {
+                    Some(key) => match Shade::new(rate(key)) {
+                        Shade::Unknown => style(ch).with(Color::DarkGrey),
+                        Shade::Good => style(ch).with(Color::Black).on(Color::Green),
+                        Shade::Fair => style(ch).with(Color::Black).on(Color::Yellow),
+                        Shade::Weak => style(ch).with(Color::Black).on(Color::Red),
+                        Shade::Poor => style(ch).with(Color::Black).on(Color::DarkRed),
+                    },
+                    None => style(ch),
+                };
+                line.push_str(&text.to_string());
+            }
+            line.push_str(&" ".repeat(width - row.len()));
+            line
+        })
+        .collect()
+}
+
+// The most frequent of the counts, in steno order when the counts are the same.
+fn most<K: Copy + Ord>(counts: &HashMap<K, usize>, top: usize) -> Vec<(K, usize)> {
+    let mut list: Vec<_> = counts.iter().map(|(&key, &count)| (key, count)).collect();
+    list.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+    list.truncate(top);
+    list
+}
+
+/// Show a report of the errors made in the last `days` days, with the `top` most frequent of
+/// each kind.
+pub fn show(db: &mut Db, days: f64, top: usize) -> Result<()> {
+    let report = Report::new(&db.get_errors(Some(days))?);
+
+    println!(
+        "{} errors in the last {} days, with {} corrected attempts",
+        report.errors, days, report.attempts
+    );
+    if report.unreadable > 0 {
+        println!("{} errors couldn't be read with the current steno system", report.unreadable);
+    }
+    println!(
+        "{} neighbour keys swapped, {} word-boundary splits, {} with too many strokes",
+        report.swaps.values().sum::<usize>(),
+        report.splits,
+        report.stroke_counts
+    );
+
+    println!();
+    println!("Most missed keys:");
+    for (key, count) in most(&report.missed, top) {
+        println!("  {:>4} {:>5}", key.to_string(), count);
+    }
+
+    println!();
+    println!("Most extra keys:");
+    for (key, count) in most(&report.extra, top) {
+        println!("  {:>4} {:>5}", key.to_string(), count);
+    }
+
+    println!();
+    println!("Most swapped keys (expected -> written):");
+    for ((expected, written), count) in most(&report.swaps, top) {
+        println!("  {:>4} -> {:<4} {:>5}", expected.to_string(), written.to_string(), count);
+    }
+    Ok(())
+}
+
+#[test]
+fn classify_mistakes() {
+    let strokes = |text: &str| StenoWord::parse(text).unwrap().0;
+    let key = |text: &str| Stroke::from_text(text).unwrap();
+    let keyboard = Keyboard::new();
+    let classify = |expected: &str, written: &str| {
+        classify(&strokes(expected), &strokes(written), &keyboard)
+    };
+
+    assert_eq!(
+        attempts(&strokes("KAS/*/KAT")),
+        [strokes("KAS")],
+    );
+    assert_eq!(
+        attempts(&strokes("A/PHAEUZ/*/*/A/PHAOEUZ")),
+        [strokes("A/PHAEUZ")],
+    );
+
+    assert_eq!(classify("KAT", "KA"), [Mistake::Missed(key("-T"))]);
+    assert_eq!(classify("KAT", "KAUT"), [Mistake::Extra(key("U"))]);
+    assert_eq!(
+        classify("KAT", "KAS"),
+        [Mistake::Swapped {
+            expected: key("-T"),
+            written: key("-S"),
+        }]
+    );
+    // The -P is too far from the -T to be a swap.
+    assert_eq!(classify("KAT", "KAP"), [Mistake::Missed(key("-T")), Mistake::Extra(key("-P"))]);
+    assert_eq!(classify("KATS", "KAT/-S"), [Mistake::Split]);
+    assert_eq!(classify("A/PHAOEUZ", "A/PHAOEU/-Z"), [Mistake::Split]);
+    assert_eq!(
+        classify("KAT", "KAT/KAT"),
+        [Mistake::StrokeCount {
+            expected: 1,
+            written: 2,
+        }]
+    );
+    assert_eq!(classify("KAT/-S", "KAT"), []);
+}
+
+#[test]
+fn key_accuracy() {
+    let key = |text: &str| Stroke::from_text(text).unwrap();
+    let errors = [ErrorEntry {
+        steno: "KAT".to_string(),
+        actual: "KAS/*/KAT".to_string(),
+    }];
+    let accuracy = Accuracy::new(Report::new(&errors), &[("KAT".to_string(), 8)]);
+
+    // The outline was meant to be written 10 times, and the -T was missed once.
+    assert_eq!(accuracy.missed(key("-T")), Some(0.1));
+    assert_eq!(accuracy.missed(key("K")), Some(0.0));
+    assert_eq!(accuracy.missed(key("-S")), None);
+    assert_eq!(accuracy.extra(key("-S")), Some(0.1));
+    assert_eq!(accuracy.extra(key("-T")), None);
+    assert_eq!(accuracy.weakest(1), [(key("-T"), 0.1, 0.0)]);
+    assert_eq!(Shade::new(accuracy.missed(key("-T"))), Shade::Poor);
+}
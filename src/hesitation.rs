@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Finding the words that are hesitated over.
+//!
+//! Each word written records how long it took to start after it was shown, and each pause between
+//! its strokes, of which the longest is reported.  A word that is written correctly, but only after
+//! a pause, is often one that isn't yet remembered well, which the errors alone don't show.
+//! Attempts that needed corrections are left out, as their time is mostly spent correcting.
+//!
+//! The times of each word are summarized by their median, so that a word that happened to be
+//! shown while the learner was away doesn't stand out.
+
+use crate::db::{Db, LatencyEntry};
+use anyhow::Result;
+use std::collections::HashMap;
+
+// Words need this many attempts before their times mean much.
+const MIN_ATTEMPTS: usize = 2;
+
+/// The times taken to write a word.
+#[derive(Debug)]
+pub struct Slow {
+    pub word: String,
+    pub steno: String,
+    pub attempts: usize,
+    /// The median time to the first stroke.
+    pub first: f64,
+    /// The median of the longest pause between strokes, for words of more than one stroke.
+    pub gap: Option<f64>,
+}
+
+impl Slow {
+    /// The total hesitation, that the words are ordered by.
+    pub fn hesitation(&self) -> f64 {
+        self.first + self.gap.unwrap_or(0.0)
+    }
+}
+
+/// Summarize the times of each word, slowest first.
+pub fn slowest(entries: &[LatencyEntry]) -> Vec<Slow> {
+    let mut words: HashMap<(&str, &str), Vec<&LatencyEntry>> = HashMap::new();
+    for entry in entries {
+        words.entry((&entry.word, &entry.steno)).or_default().push(entry);
+    }
+
+    let mut result: Vec<_> = words
+        .into_iter()
+        .filter(|(_, entries)| entries.len() >= MIN_ATTEMPTS)
+        .map(|((word, steno), entries)| Slow {
+            word: word.to_string(),
+            steno: steno.to_string(),
+            attempts: entries.len(),
+            first: median(entries.iter().map(|entry| entry.first)).unwrap(),
+            gap: median(entries.iter().filter_map(|entry| entry.longest_gap())),
+        })
+        .collect();
+    result.sort_by(|a, b| {
+        b.hesitation()
+            .total_cmp(&a.hesitation())
+            .then_with(|| a.word.cmp(&b.word))
+    });
+    result
+}
+
+fn median<I: Iterator<Item = f64>>(values: I) -> Option<f64> {
+    let mut values: Vec<_> = values.collect();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        Some((values[mid - 1] + values[mid]) / 2.0)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Print the words that took the longest to write in the last `days` days.
+pub fn show(db: &mut Db, days: f64, top: usize) -> Result<()> {
+    let entries = db.get_latencies(days)?;
+    let slow = slowest(&entries);
+
+    println!(
+        "{} words written without corrections in the last {} days, {} of them at least {} times",
+        entries.len(),
+        days,
+        slow.len(),
+        MIN_ATTEMPTS
+    );
+    println!();
+    println!("Slowest words (median seconds):");
+    println!("  start  pause  tries  word");
+    for word in slow.iter().take(top) {
+        let gap = word.gap.map_or("".to_string(), |gap| format!("{:.1}", gap));
+        println!(
+            "  {:>5.1}  {:>5}  {:>5}  {} ({})",
+            word.first, gap, word.attempts, word.word, word.steno
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn slowest_words() {
+    let entry = |word: &str, steno: &str, first: f64, gaps: &[f64]| LatencyEntry {
+        word: word.to_string(),
+        steno: steno.to_string(),
+        first,
+        gaps: gaps.to_vec(),
+    };
+    let entries = [
+        entry("cat", "KAT", 0.5, &[]),
+        entry("cat", "KAT", 0.7, &[]),
+        // Once away from the keyboard doesn't make a word slow.
+        entry("cat", "KAT", 300.0, &[]),
+        entry("dog", "TKOG", 1.0, &[]),
+        entry("dog", "TKOG", 2.0, &[]),
+        entry("catalog", "KAT/A/HROG", 0.5, &[2.0, 0.5]),
+        entry("catalog", "KAT/A/HROG", 0.7, &[0.25, 1.0]),
+        // Only written once.
+        entry("cap", "KAP", 10.0, &[]),
+    ];
+
+    let slow = slowest(&entries);
+    let summary: Vec<_> = slow
+        .iter()
+        .map(|slow| (slow.word.as_str(), slow.attempts, slow.first, slow.gap))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("catalog", 2, 0.6, Some(1.5)),
+            ("dog", 2, 1.5, None),
+            ("cat", 3, 0.7, None),
+        ]
+    );
+}
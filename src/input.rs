@@ -5,113 +5,199 @@
 //! dictionary, and configuring plover to output a space after each stroke, we get the advantage of
 //! seeing the full strokes.
 //!
-//! However, Plover still tracks how many characters it has typed, and pressing '*' will remove
-//! that many characters.  To accomodate this, we will keep track of how many characters are
-//! received, including the space, and when backspace is received, subtract from that until we
-//! cross a boundary.
+//! However, Plover still tracks what it has typed, and pressing '*' will remove it with
+//! backspaces.  To accomodate this, we keep track of the strokes that have been typed.  A
+//! backspace that removes the space after a stroke undoes that stroke, and puts its text back to
+//! be deleted by the backspaces that follow, or to have more typed after it, as Plover does when
+//! it only retypes the end of a stroke.  Backspaces within a stroke just delete from it.
 //!
 //! Although this code will still handle the case where '*' generates numerous backspaces, we also
 //! handle the case where the Plover dictionary contains an entry for '*' that just emits a star and
 //! a space.
+//!
+//! Strokes can also come from other sources: keyboard steno, a steno machine on a serial port,
+//! speaking Gemini PR or TX Bolt, or Plover's stroke log, any of which avoids needing to set up
+//! Plover specially, or a tape written during an earlier session, which replays it without anyone
+//! at the keyboard.  Tests drive sessions from a script.
 
 use anyhow::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEvent};
 use std::collections::VecDeque;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use crate::db::get_now;
+use crate::stroke::{system, Diagnostic, Mode, Stroke};
+
+#[cfg(unix)]
+pub use gemini::GeminiReader;
+pub use keyboard::{KeyboardMode, KeyboardSteno, Layout};
+pub use log::LogFollower;
+#[cfg(test)]
+pub use script::Script;
+pub use tape::TapeReplay;
+#[cfg(unix)]
+pub use txbolt::TxBoltReader;
+
+// Steno machines are only supported where serial ports can be set up with termios.
+#[cfg(unix)]
+mod gemini;
+mod keyboard;
+mod log;
+// Scripts are only used to drive sessions in tests.  To demonstrate a session, replay a tape,
+// which also keeps the timing of the strokes.
+#[cfg(test)]
+mod script;
+#[cfg(unix)]
+mod serial;
+mod tape;
+#[cfg(unix)]
+mod txbolt;
 
-use crate::stroke::Stroke;
+/// A source of strokes.
+pub trait StrokeSource {
+    /// Read the next stroke.  Waits at most `timeout` before returning a Timeout.  Returns Exit
+    /// when the user wishes to exit, or there are no more strokes.
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value>;
+
+    /// Called before the terminal is put back, for sources that change how it reports keys.
+    fn stop(&mut self) {}
+}
 
 pub struct StrokeReader {
-    // Character sizes for the strokes that have been seen.  Handles the case where Plover uses
-    // backspace to implement '*'.
-    sizes: VecDeque<usize>,
+    // The text of the strokes that have been typed, so that backspaces can undo them.
+    strokes: VecDeque<String>,
 
     // The characters seen so far.  In case we get resize events or timeouts interspersed with the
     // characters of a stroke.
     buffer: String,
 }
 
+#[derive(Debug)]
 pub enum Value {
-    Stroke(Stroke),
+    /// A stroke, and when it was written, in seconds, as given by `get_now`.
+    Stroke(Stroke, f64),
+    /// Input that couldn't be read as a stroke.
+    Invalid(Diagnostic),
+    /// Input that isn't part of a stroke, described for the user.
+    Unexpected(String),
     Resize(u16, u16),
     Timeout,
     Exit,
 }
 
+impl Value {
+    /// A stroke written just now.
+    pub fn stroke(stroke: Stroke) -> Value {
+        Value::Stroke(stroke, get_now())
+    }
+}
+
 impl StrokeReader {
     pub fn new() -> StrokeReader {
         StrokeReader {
-            sizes: VecDeque::new(),
+            strokes: VecDeque::new(),
             buffer: String::new(),
         }
     }
 
-    /// Attempt to read a stroke from the input.  Returns Ok(None) when Escape is pressed, to
-    /// indicate the user wishes to exit.  The timeout specifies how long this should wait before
-    /// returning a Timeout.
-    pub fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+    // Handle a key typed by Plover.  Returns a value when the key completes one.
+    fn key(&mut self, code: KeyCode) -> Option<Value> {
+        match code {
+            KeyCode::Esc => Some(Value::Exit),
+            KeyCode::Char(' ') => {
+                if self.buffer.is_empty() {
+                    return Some(Value::Unexpected("A space without a stroke".into()));
+                }
+                let text = std::mem::take(&mut self.buffer);
+                let parsed = Stroke::parse(&text, Mode::Lenient);
+                self.strokes.push_back(text);
+                while self.strokes.len() > 100 {
+                    self.strokes.pop_front();
+                }
+                Some(match parsed {
+                    Ok(parsed) => Value::stroke(parsed.stroke),
+                    Err(diag) => Value::Invalid(diag),
+                })
+            }
+            KeyCode::Char(ch) => {
+                self.buffer.push(ch);
+                None
+            }
+            KeyCode::Backspace => {
+                if self.buffer.pop().is_some() {
+                    return None;
+                }
+                // Removing the space after a stroke undoes it.
+                let text = match self.strokes.pop_back() {
+                    Some(text) => text,
+                    None => {
+                        let message = "A backspace before any strokes";
+                        return Some(Value::Unexpected(message.into()));
+                    }
+                };
+                self.buffer = text;
+                Some(match system::current().undo() {
+                    Some(undo) => Value::stroke(undo),
+                    None => {
+                        let message = "A backspace, but the steno system has no undo";
+                        Value::Unexpected(message.into())
+                    }
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl StrokeSource for StrokeReader {
+    /// Attempt to read a stroke from the keyboard.  Returns Exit when Escape is pressed, to
+    /// indicate the user wishes to exit.
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        let start = Instant::now();
         loop {
-            if !event::poll(timeout)? {
+            let wait = timeout.saturating_sub(start.elapsed());
+            if !event::poll(wait)? {
                 return Ok(Value::Timeout);
             }
 
             match event::read()? {
-                Event::Key(KeyEvent {
-                    code: KeyCode::Esc, ..
-                }) => return Ok(Value::Exit),
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(' '),
-                    ..
-                }) => break,
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char(ch),
-                    ..
-                }) => self.buffer.push(ch),
-                Event::Key(KeyEvent {
-                    code: KeyCode::Backspace,
-                    ..
-                }) => {
-                    if self.buffer.is_empty() {
-                        // Pop a stroke.
-                        let count = if let Some(count) = self.sizes.pop_back() {
-                            count
-                        } else {
-                            println!("Warning, backspace before input\r");
-                            continue;
-                        };
-                        match count {
-                            0 => panic!("Should never push 0"),
-                            1 => {
-                                // Word boundary, return the deletion up, and leave the stroke
-                                // popped.
-                                // println!("Return *\r");
-                                return Ok(Value::Stroke(Stroke::from_text("*")?));
-                            }
-                            n => {
-                                // Not word boundary, just reduce the count.
-                                self.sizes.push_back(n - 1);
-                            }
-                        }
-                    } else {
-                        println!("TODO: Backspace in a word");
-                        return Ok(Value::Exit);
+                Event::Key(KeyEvent { code, .. }) => {
+                    if let Some(value) = self.key(code) {
+                        return Ok(value);
                     }
                 }
-                Event::Resize(x, y) => {
-                    return Ok(Value::Resize(x, y));
-                }
+                Event::Resize(x, y) => return Ok(Value::Resize(x, y)),
                 _ => (),
             }
         }
+    }
+}
 
-        self.sizes.push_back(self.buffer.len() + 1);
-        while self.sizes.len() > 100 {
-            self.sizes.pop_front();
-        }
+#[test]
+fn plover_corrections() {
+    let mut reader = StrokeReader::new();
+    let mut typed = |text: &str| -> Vec<String> {
+        let keys = text.chars().map(|ch| match ch {
+            '<' => KeyCode::Backspace,
+            ch => KeyCode::Char(ch),
+        });
+        keys.filter_map(|code| reader.key(code))
+            .map(|value| match value {
+                Value::Stroke(stroke, _) => stroke.to_string(),
+                Value::Unexpected(_) => "?".to_string(),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    };
 
-        let stroke = Stroke::from_text(&self.buffer)?;
-        self.buffer.clear();
+    assert_eq!(typed("KAT TKOG "), ["KAT", "TKOG"]);
+    // '*' removes the last stroke, and then the one before it.
+    assert_eq!(typed("<<<<<"), ["*"]);
+    assert_eq!(typed("<<<<"), ["*"]);
+    assert_eq!(typed("<"), ["?"]);
 
-        Ok(Value::Stroke(stroke))
-    }
+    // Deleting within a stroke, and Plover retyping just the end of one.
+    assert_eq!(typed("KAX<T "), ["KAT"]);
+    assert_eq!(typed("<<P "), ["*", "KAP"]);
+    assert_eq!(typed(" "), ["?"]);
 }
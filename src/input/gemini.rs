@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Reading strokes from a steno machine that speaks Gemini PR.
+//!
+//! Gemini PR is spoken by most hobbyist steno machines, over a USB serial port.  Each stroke is
+//! sent as a packet of 6 bytes, with the high bit set on the first byte only, and the other 7 bits
+//! of each byte giving the keys, in this order:
+//!
+//! ```text
+//! Fn  #1  #2  #3  #4   #5   #6
+//! S1- S2- T-  K-  P-   W-   H-
+//! R-  A-  O-  *1  *2   res1 res2
+//! pwr *3  *4  -E  -U   -F   -R
+//! -P  -B  -L  -G  -T   -S   -D
+//! #7  #8  #9  #A  #B   #C   -Z
+//! ```
+//!
+//! As with Plover, all of the number keys are the number bar, both S keys are S, and all of the
+//! star keys are the star.  The keys are matched to the steno system by name, and any the system
+//! doesn't have are ignored, other than the one chosen to exit.
+
+use anyhow::{bail, Result};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::serial::{self, Serial};
+use super::{StrokeSource, Value};
+use crate::stroke::{system, Stroke};
+
+/// The keys of a Gemini PR packet, in the order of their bits.
+pub static KEYS: [&str; 42] = [
+    "Fn", "#1", "#2", "#3", "#4", "#5", "#6", //
+    "S1-", "S2-", "T-", "K-", "P-", "W-", "H-", //
+    "R-", "A-", "O-", "*1", "*2", "res1", "res2", //
+    "pwr", "*3", "*4", "-E", "-U", "-F", "-R", //
+    "-P", "-B", "-L", "-G", "-T", "-S", "-D", //
+    "#7", "#8", "#9", "#A", "#B", "#C", "-Z", //
+];
+
+pub struct GeminiReader {
+    port: Serial,
+    // Bytes that have arrived, but don't yet make a whole packet.
+    buffer: Vec<u8>,
+    // The key, from KEYS, that exits, rather than being part of a stroke.
+    exit: usize,
+}
+
+impl GeminiReader {
+    /// Read strokes from the machine on the serial port at `path`.  Pressing `exit_key`, one of
+    /// the names in KEYS, exits.
+    pub fn open<P: AsRef<Path>>(path: P, exit_key: &str) -> Result<GeminiReader> {
+        let exit = match KEYS.iter().position(|&key| key == exit_key) {
+            Some(exit) => exit,
+            None => bail!(
+                "Unknown Gemini PR key {:?}, expecting one of {}",
+                exit_key,
+                KEYS.join(" ")
+            ),
+        };
+        Ok(GeminiReader {
+            port: Serial::open(path, libc::B9600)?,
+            buffer: vec![],
+            exit,
+        })
+    }
+
+    // Wait up to `timeout` for a packet from the machine.
+    fn read_packet(&mut self, timeout: Duration) -> Result<Option<Value>> {
+        let start = Instant::now();
+        loop {
+            // Skip anything before the start of a packet, such as the end of one that was cut off
+            // when the port was opened.
+            let skip = self.buffer.iter().position(|b| b & 0x80 != 0).unwrap_or(self.buffer.len());
+            self.buffer.drain(..skip);
+
+            if self.buffer.len() >= 6 {
+                match self.buffer[1..6].iter().position(|b| b & 0x80 != 0) {
+                    // A packet that was cut short by the start of the next one.
+                    Some(short) => drop(self.buffer.drain(..short + 1)),
+                    None => {
+                        let packet: Vec<u8> = self.buffer.drain(..6).collect();
+                        if let Some(value) = self.decode(&packet) {
+                            return Ok(Some(value));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let wait = timeout.saturating_sub(start.elapsed());
+            if self.port.read(&mut self.buffer, wait)? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    // Decode a packet to the stroke it gives.  Packets with only keys the system doesn't have are
+    // skipped.
+    fn decode(&self, packet: &[u8]) -> Option<Value> {
+        let pressed = (0..KEYS.len()).filter(|&key| packet[key / 7] & (0x40 >> (key % 7)) != 0);
+
+        let system = system::current();
+        let mut stroke = Stroke::default();
+        for key in pressed {
+            if key == self.exit {
+                return Some(Value::Exit);
+            }
+            if let Some(key) = system.key_named(plover_name(KEYS[key])) {
+                stroke = stroke.union(key);
+            }
+        }
+        if stroke == Stroke::default() {
+            None
+        } else {
+            Some(Value::stroke(stroke))
+        }
+    }
+}
+
+// The name Plover gives a Gemini PR key.
+fn plover_name(key: &str) -> &str {
+    match key {
+        "S1-" | "S2-" => "S-",
+        "*1" | "*2" | "*3" | "*4" => "*",
+        _ if key.starts_with('#') => "#",
+        _ => key,
+    }
+}
+
+impl StrokeSource for GeminiReader {
+    /// Read a stroke from the machine.  Escape on the keyboard also exits.
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        serial::read_machine(timeout, |wait| self.read_packet(wait))
+    }
+}
+
+// Build the packet for the given keys.
+#[cfg(test)]
+fn packet(keys: &[&str]) -> Vec<u8> {
+    let mut packet = vec![0x80, 0, 0, 0, 0, 0];
+    for name in keys {
+        let key = KEYS.iter().position(|k| k == name).unwrap();
+        packet[key / 7] |= 0x40 >> (key % 7);
+    }
+    packet
+}
+
+#[test]
+fn gemini_pty() {
+    use std::io::Write;
+
+    let (mut machine, path) = super::serial::pty_pair();
+    let mut reader = GeminiReader::open(&path, "Fn").unwrap();
+    assert!(GeminiReader::open(&path, "Esc").is_err());
+
+    let mut read = |bytes: Vec<u8>| {
+        machine.write_all(&bytes).unwrap();
+        match reader.read_packet(Duration::from_secs(1)).unwrap() {
+            Some(Value::Stroke(stroke, _)) => stroke.to_string(),
+            other => format!("{:?}", other),
+        }
+    };
+
+    assert_eq!(read(packet(&["K-", "A-", "-T"])), "KAT");
+    assert_eq!(read(packet(&["#4", "S2-", "-T"])), "1-9");
+    assert_eq!(read(packet(&["*3"])), "*");
+    assert_eq!(read(packet(&["S1-", "T-", "K-", "P-", "W-", "H-", "R-", "-Z"])), "STKPWHR-Z");
+
+    // The end of a packet, and one cut short, are skipped.
+    let mut bytes = vec![0x01, 0x02];
+    bytes.extend(&packet(&["T-"])[..3]);
+    bytes.extend(packet(&["-D"]));
+    assert_eq!(read(bytes), "-D");
+
+    // Keys the system doesn't have don't make a stroke.
+    let mut bytes = packet(&["pwr", "res1"]);
+    bytes.extend(packet(&["O-"]));
+    assert_eq!(read(bytes), "O");
+
+    assert_eq!(read(packet(&["Fn", "K-"])), "Some(Exit)");
+    assert_eq!(read(vec![]), "None");
+}
@@ -0,0 +1,439 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Keyboard steno, so that steno can be written on an ordinary keyboard without Plover.
+//!
+//! The keys of the keyboard are given steno keys by a layout, which by default is the one Plover
+//! uses for QWERTY keyboards.  Most keyboards can't send every key of a chord, and terminals only
+//! report keys as they are pressed, so by default strokes are arpeggiated: the keys are pressed
+//! one after the other, and space ends the stroke.
+//!
+//! Terminals that support the kitty keyboard protocol can report when keys are released.  With
+//! these, keys can be chorded as on a steno machine, with the stroke ending when all of the keys
+//! have been released.  This still needs a keyboard that sends all of the keys that are held.
+
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use crossterm::terminal::{self, disable_raw_mode, enable_raw_mode};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::{StrokeSource, Value};
+use crate::stroke::{system, Stroke};
+
+/// The layout Plover uses for QWERTY keyboards.
+pub static QWERTY: &str = include_str!("../../layouts/qwerty.toml");
+
+/// How the strokes are written on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardMode {
+    /// Keys are pressed one at a time, and space ends the stroke.
+    Arpeggiate,
+    /// Keys are pressed together, and the stroke ends when they have all been released.
+    Chord,
+}
+
+impl FromStr for KeyboardMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<KeyboardMode> {
+        match s {
+            "arpeggiate" => Ok(KeyboardMode::Arpeggiate),
+            "chord" => Ok(KeyboardMode::Chord),
+            _ => Err(anyhow!("Unknown keyboard mode {:?}, expecting arpeggiate or chord", s)),
+        }
+    }
+}
+
+/// The steno key that each key of the keyboard presses.
+#[derive(Debug)]
+pub struct Layout {
+    keys: HashMap<char, Stroke>,
+}
+
+impl Layout {
+    /// The built-in QWERTY layout.
+    pub fn qwerty() -> Result<Layout> {
+        Layout::parse(QWERTY)
+    }
+
+    /// Load a layout from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Layout> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        Layout::parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    /// Parse a layout, given as a table from the name of each steno key to the keyboard keys that
+    /// press it.  The names are those of the current steno system.
+    pub fn parse(text: &str) -> Result<Layout> {
+        let def: BTreeMap<String, Vec<String>> = toml::from_str(text)?;
+
+        let system = system::current();
+        let mut keys = HashMap::new();
+        for (name, chars) in def {
+            let stroke = match system.key_named(&name) {
+                Some(stroke) => stroke,
+                None => bail!("Unknown steno key {:?}", name),
+            };
+            for text in chars {
+                let mut chars = text.chars();
+                let ch = match (chars.next(), chars.next()) {
+                    (Some(ch), None) if ch != ' ' => ch,
+                    _ => bail!(
+                        "Key {:?} for {:?} must be a single character, other than space",
+                        text,
+                        name
+                    ),
+                };
+                if keys.insert(ch, stroke).is_some() {
+                    bail!("Key {:?} is given more than once", text);
+                }
+            }
+        }
+        Ok(Layout { keys })
+    }
+}
+
+/// Builds strokes from the keys pressed on the keyboard.
+#[derive(Debug)]
+pub struct Chorder {
+    mode: KeyboardMode,
+    layout: Layout,
+    // The keys of the stroke so far.
+    keys: Stroke,
+    // The keyboard keys that are held, when chording.
+    held: HashSet<char>,
+}
+
+impl Chorder {
+    pub fn new(mode: KeyboardMode, layout: Layout) -> Chorder {
+        Chorder {
+            mode,
+            layout,
+            keys: Stroke::default(),
+            held: HashSet::new(),
+        }
+    }
+
+    /// A key has been pressed.  Returns the stroke, if this ends it.
+    pub fn press(&mut self, ch: char) -> Option<Stroke> {
+        let ch = ch.to_ascii_lowercase();
+        if ch == ' ' && self.mode == KeyboardMode::Arpeggiate {
+            return self.take();
+        }
+        if let Some(&key) = self.layout.keys.get(&ch) {
+            self.keys = self.keys.union(key);
+            if self.mode == KeyboardMode::Chord {
+                self.held.insert(ch);
+            }
+        }
+        None
+    }
+
+    /// A key has been released.  Returns the stroke, if this was the last key held.
+    pub fn release(&mut self, ch: char) -> Option<Stroke> {
+        if self.held.remove(&ch.to_ascii_lowercase()) && self.held.is_empty() {
+            return self.take();
+        }
+        None
+    }
+
+    /// Forget the keys of the stroke so far.
+    pub fn clear(&mut self) {
+        self.keys = Stroke::default();
+        self.held.clear();
+    }
+
+    fn take(&mut self) -> Option<Stroke> {
+        let keys = std::mem::take(&mut self.keys);
+        if keys == Stroke::default() {
+            None
+        } else {
+            Some(keys)
+        }
+    }
+}
+
+// The kitty keyboard protocol flags: disambiguate escapes, report releases, and report every key
+// as an escape sequence, so that the releases of ordinary keys are seen too.
+const KITTY_FLAGS: u8 = 1 | 2 | 8;
+
+pub struct KeyboardSteno {
+    chorder: Chorder,
+    // When chording, the terminal is read directly, as crossterm doesn't know about releases.
+    buffer: Vec<u8>,
+    // Whether the terminal has been asked to report releases, and its size, to notice resizes
+    // while it is being read directly.
+    reporting: bool,
+    size: (u16, u16),
+}
+
+impl KeyboardSteno {
+    /// Read strokes written on the keyboard, with the given layout.  Chording fails if the
+    /// terminal can't report releases.
+    pub fn new(mode: KeyboardMode, layout: Layout) -> Result<KeyboardSteno> {
+        if mode == KeyboardMode::Chord && !reports_releases()? {
+            bail!(
+                "This terminal doesn't report when keys are released, which is needed to chord.  \
+                 Use --keyboard arpeggiate, or a terminal that supports the kitty keyboard \
+                 protocol, such as kitty, foot or WezTerm"
+            );
+        }
+        Ok(KeyboardSteno {
+            chorder: Chorder::new(mode, layout),
+            buffer: vec![],
+            reporting: false,
+            size: terminal::size().unwrap_or_default(),
+        })
+    }
+
+    // Read arpeggiated strokes, as ordinary key presses.
+    fn read_presses(&mut self, timeout: Duration) -> Result<Value> {
+        let start = Instant::now();
+        loop {
+            let wait = timeout.saturating_sub(start.elapsed());
+            if !event::poll(wait)? {
+                return Ok(Value::Timeout);
+            }
+            match event::read()? {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                }) => return Ok(Value::Exit),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char(ch),
+                    ..
+                }) => {
+                    if let Some(stroke) = self.chorder.press(ch) {
+                        return Ok(Value::stroke(stroke));
+                    }
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Backspace,
+                    ..
+                }) => self.chorder.clear(),
+                Event::Resize(x, y) => return Ok(Value::Resize(x, y)),
+                _ => (),
+            }
+        }
+    }
+
+    // Read chorded strokes, from the presses and releases reported by the terminal.
+    fn read_chords(&mut self, timeout: Duration) -> Result<Value> {
+        if !self.reporting {
+            write_terminal(&format!("\x1b[>{}u", KITTY_FLAGS))?;
+            self.reporting = true;
+        }
+
+        let start = Instant::now();
+        loop {
+            while let Some((code, kind)) = next_key(&mut self.buffer) {
+                match (code, kind) {
+                    ('\x1b', Kind::Press) => return Ok(Value::Exit),
+                    ('\x7f', Kind::Press) => self.chorder.clear(),
+                    (ch, Kind::Press) => {
+                        self.chorder.press(ch);
+                    }
+                    (ch, Kind::Release) => {
+                        if let Some(stroke) = self.chorder.release(ch) {
+                            return Ok(Value::stroke(stroke));
+                        }
+                    }
+                    (_, Kind::Repeat) => (),
+                }
+            }
+
+            let size = terminal::size()?;
+            if size != self.size {
+                self.size = size;
+                return Ok(Value::Resize(size.0, size.1));
+            }
+
+            let wait = timeout.saturating_sub(start.elapsed());
+            if wait.is_zero() {
+                return Ok(Value::Timeout);
+            }
+            read_terminal(&mut self.buffer, wait.min(Duration::from_millis(50)))?;
+        }
+    }
+}
+
+impl StrokeSource for KeyboardSteno {
+    /// Read a stroke written on the keyboard.  Escape exits, and backspace forgets the keys of
+    /// the stroke so far.
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        match self.chorder.mode {
+            KeyboardMode::Arpeggiate => self.read_presses(timeout),
+            KeyboardMode::Chord => self.read_chords(timeout),
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.reporting {
+            let _ = write_terminal("\x1b[<u");
+            self.reporting = false;
+        }
+    }
+}
+
+impl Drop for KeyboardSteno {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn write_terminal(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(text.as_bytes())?;
+    stdout.flush()
+}
+
+// Wait up to `timeout` for bytes from the terminal, and add them to `buffer`.  Returns how many
+// bytes were read, which is 0 on a timeout.
+#[cfg(unix)]
+fn read_terminal(buffer: &mut Vec<u8>, timeout: Duration) -> Result<usize> {
+    use std::fs::File;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+
+    // Safety: stdin stays open for the life of the program, and the File is never dropped, so it
+    // won't be closed.
+    let mut stdin = ManuallyDrop::new(unsafe { File::from_raw_fd(0) });
+    super::serial::read_ready(&mut stdin, buffer, timeout)
+}
+
+#[cfg(not(unix))]
+fn read_terminal(_buffer: &mut Vec<u8>, _timeout: Duration) -> Result<usize> {
+    bail!("Chording reads the terminal directly, which is only supported on Unix systems")
+}
+
+// Ask the terminal whether it supports the kitty keyboard protocol.  The request for the
+// protocol's flags is followed by one for the device attributes, which every terminal answers,
+// so that there is no need to wait long for an answer that won't come.
+fn reports_releases() -> Result<bool> {
+    if !crossterm::tty::IsTty::is_tty(&io::stdin()) {
+        return Ok(false);
+    }
+    enable_raw_mode()?;
+    let mut reply = vec![];
+    let result = (|| -> Result<bool> {
+        write_terminal("\x1b[?u\x1b[c")?;
+        let start = Instant::now();
+        let wait = Duration::from_millis(500);
+        while start.elapsed() < wait {
+            read_terminal(&mut reply, wait - start.elapsed())?;
+            if reply.contains(&b'c') {
+                return Ok(has_flags(&reply));
+            }
+        }
+        Ok(false)
+    })();
+    disable_raw_mode()?;
+    result
+}
+
+// Does the terminal's reply include the kitty keyboard flags?
+fn has_flags(reply: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(reply);
+    text.split("\x1b[?").any(|part| {
+        let digits = part.trim_start_matches(|c: char| c.is_ascii_digit());
+        digits.len() < part.len() && digits.starts_with('u')
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    Press,
+    Repeat,
+    Release,
+}
+
+// Take the next key event from what the terminal has sent, when it uses the kitty keyboard
+// protocol.  Keys are sent as `ESC [ code ; modifiers : kind u`, where the modifiers and kind
+// can be left out.  Other escape sequences, such as for the arrow keys, are skipped.
+fn next_key(buffer: &mut Vec<u8>) -> Option<(char, Kind)> {
+    loop {
+        let start = buffer.iter().position(|&b| b == 0x1b).unwrap_or(buffer.len());
+        buffer.drain(..start);
+        if buffer.len() < 2 {
+            return None;
+        }
+        if buffer[1] != b'[' {
+            buffer.drain(..1);
+            continue;
+        }
+
+        // The sequence ends with its first byte in the range '@' to '~'.
+        let end = buffer[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+        let sequence: Vec<u8> = buffer.drain(..=end).collect();
+        if sequence[end] != b'u' {
+            continue;
+        }
+
+        let params = String::from_utf8_lossy(&sequence[2..end]).into_owned();
+        let mut params = params.split(';');
+        let code = params.next().and_then(|p| p.split(':').next()?.parse::<u32>().ok());
+        let kind = match params.next().and_then(|p| p.split(':').nth(1)) {
+            None | Some("1") => Kind::Press,
+            Some("2") => Kind::Repeat,
+            _ => Kind::Release,
+        };
+        if let Some(ch) = code.and_then(char::from_u32) {
+            return Some((ch, kind));
+        }
+    }
+}
+
+#[test]
+fn keyboard_chords() {
+    let layout = || Layout::qwerty().unwrap();
+    let written = |chorder: &mut Chorder, events: &[(char, bool)]| -> Vec<String> {
+        let mut strokes = vec![];
+        for &(ch, press) in events {
+            let stroke = if press { chorder.press(ch) } else { chorder.release(ch) };
+            strokes.extend(stroke.map(|stroke| stroke.to_string()));
+        }
+        strokes
+    };
+
+    // Arpeggiating, space ends each stroke, and keys outside the layout are ignored.
+    let mut arpeggio = Chorder::new(KeyboardMode::Arpeggiate, layout());
+    let keys: Vec<_> = "sxcp wsvl  ".chars().map(|ch| (ch, true)).collect();
+    assert_eq!(written(&mut arpeggio, &keys), ["KAT", "TKOG"]);
+    arpeggio.press('s');
+    arpeggio.clear();
+    assert_eq!(
+        written(&mut arpeggio, &[('1', true), ('A', true), ('p', true), (' ', true)]),
+        ["1-9"]
+    );
+
+    // Chording, the stroke ends once every key held has been released.
+    let mut chord = Chorder::new(KeyboardMode::Chord, layout());
+    let keys = [('s', true), ('c', true), ('c', false), ('p', true), ('s', false), ('p', false)];
+    assert_eq!(written(&mut chord, &keys), ["KAT"]);
+    let keys = [('t', true), ('y', true), ('t', false), ('x', true), ('y', false), ('x', false)];
+    assert_eq!(written(&mut chord, &keys), ["*"]);
+
+    assert!(Layout::parse("\"Q-\" = [\"q\"]").is_err());
+    assert!(Layout::parse("\"S-\" = [\"q\"]\n\"T-\" = [\"q\"]").is_err());
+    assert!(Layout::parse("\"S-\" = [\"qq\"]").is_err());
+}
+
+#[test]
+fn kitty_keys() {
+    let mut buffer = b"\x1b[115u\x1b[115;1:3u\x1b[97;2:2u\x1b[1;1:1A\x1b[27u\x1b[11".to_vec();
+    let keys: Vec<_> = std::iter::from_fn(|| next_key(&mut buffer)).collect();
+    assert_eq!(
+        keys,
+        [('s', Kind::Press), ('s', Kind::Release), ('a', Kind::Repeat), ('\x1b', Kind::Press)]
+    );
+    // The start of a sequence is kept until the rest arrives.
+    assert_eq!(buffer, b"\x1b[11");
+    buffer.extend(b"2;1:3u");
+    assert_eq!(next_key(&mut buffer), Some(('p', Kind::Release)));
+
+    assert!(has_flags(b"\x1b[?0u\x1b[?62;22c"));
+    assert!(!has_flags(b"\x1b[?62;22c"));
+}
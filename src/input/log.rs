@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Reading strokes from Plover's stroke log.
+//!
+//! When Plover is set to log strokes, each stroke is appended to its `strokes.log`, whatever the
+//! dictionaries do with it.  Following this file gives the strokes without having to configure
+//! Plover specially, so it can be left set up for normal writing.  The lines look like:
+//!
+//! ```text
+//! 2026-10-16 10:00:00.123 Stroke(KAT : ['K-', 'A-', '-T'])
+//! ```
+//!
+//! Older versions of Plover give only the list of keys, as in `Stroke(K- A- -T)`.  Any other file
+//! that strokes are appended to, one per line, either as steno or as a tape written by `--tape`,
+//! can be followed as well.
+//!
+//! The keyboard is still watched, for Escape, and so that whatever Plover types into the terminal
+//! is ignored.
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::{StrokeSource, Value};
+use crate::stroke::{system, Diagnostic, Mode, Stroke};
+
+pub struct LogFollower {
+    path: PathBuf,
+    file: File,
+    // How much of the file has been read.  This is always the end of a line, so that a line that
+    // is only partly written, which may end within a character, is read again once it is whole.
+    pos: u64,
+    // Strokes that have been read, but not yet returned.
+    pending: VecDeque<Value>,
+}
+
+impl LogFollower {
+    /// Follow the file at `path`.  Only strokes appended after this are read.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<LogFollower> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+        let pos = file.seek(SeekFrom::End(0))?;
+        Ok(LogFollower {
+            path,
+            file,
+            pos,
+            pending: VecDeque::new(),
+        })
+    }
+
+    // Read whatever has been added to the file.  If the file has become shorter, Plover has
+    // started a new log, which is read from the start.
+    fn read_new(&mut self) -> Result<()> {
+        if fs::metadata(&self.path)?.len() < self.pos {
+            self.file = File::open(&self.path)?;
+            self.pos = 0;
+        }
+
+        let mut bytes = vec![];
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        self.file.read_to_end(&mut bytes)?;
+        let end = match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(()),
+        };
+        self.pos += end as u64;
+        for line in bytes[..end].split(|&b| b == b'\n') {
+            if let Some(value) = parse_line(&String::from_utf8_lossy(line)) {
+                self.pending.push_back(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the stroke from a line of the log.  Lines that don't have a stroke are skipped.
+pub fn parse_line(line: &str) -> Option<Value> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    let value = |parsed: std::result::Result<Stroke, Diagnostic>| match parsed {
+        Ok(stroke) => Value::stroke(stroke),
+        Err(diag) => Value::Invalid(diag),
+    };
+
+    if let Some(start) = line.find("Stroke(") {
+        let inner = &line[start + 7..];
+        let inner = &inner[..inner.rfind(')')?];
+        return Some(match inner.split_once(" : ") {
+            Some((steno, _)) => value(Stroke::parse(steno.trim(), Mode::Lenient).map(|p| p.stroke)),
+            None => {
+                let system = system::current();
+                let keys = inner
+                    .split_whitespace()
+                    .map(|name| system.key_named(name))
+                    .collect::<Option<Vec<_>>>()?;
+                Value::stroke(keys.into_iter().fold(Stroke::default(), Stroke::union))
+            }
+        });
+    }
+
+    if let Some(stroke) = Stroke::from_tape(line.get(..Stroke::tape_width()).unwrap_or(line)) {
+        return Some(Value::stroke(stroke));
+    }
+    let steno = line.trim();
+    if steno.is_empty() {
+        return None;
+    }
+    Stroke::parse(steno, Mode::Lenient).ok().map(|p| Value::stroke(p.stroke))
+}
+
+impl StrokeSource for LogFollower {
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        let start = Instant::now();
+        loop {
+            if let Some(value) = self.pending.pop_front() {
+                return Ok(value);
+            }
+            self.read_new()?;
+            if !self.pending.is_empty() {
+                continue;
+            }
+
+            let wait = timeout.saturating_sub(start.elapsed());
+            if wait.is_zero() {
+                return Ok(Value::Timeout);
+            }
+            if event::poll(wait.min(Duration::from_millis(50)))? {
+                match event::read()? {
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Esc, ..
+                    }) => return Ok(Value::Exit),
+                    Event::Resize(x, y) => return Ok(Value::Resize(x, y)),
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn log_lines() {
+    let stroke = |line| match parse_line(line) {
+        Some(Value::Stroke(stroke, _)) => Some(stroke.to_string()),
+        Some(other) => panic!("{:?}", other),
+        None => None,
+    };
+    assert_eq!(
+        stroke("2026-10-16 10:00:00.123 Stroke(KAT : ['K-', 'A-', '-T'])\n").as_deref(),
+        Some("KAT")
+    );
+    assert_eq!(
+        stroke("2026-10-16 10:00:00.123 Stroke(1-9 : ['#', 'S-', '-T'])").as_deref(),
+        Some("1-9")
+    );
+    assert_eq!(stroke("2016-01-01 10:00:00,000 Stroke(K- A- -T)").as_deref(), Some("KAT"));
+    assert_eq!(stroke("2016-01-01 10:00:00,000 Stroke(# S- -T)").as_deref(), Some("1-9"));
+    assert_eq!(stroke("TKOG").as_deref(), Some("TKOG"));
+    let tape = format!("{} 1.500", Stroke::from_text("KAT").unwrap().to_tape());
+    assert_eq!(stroke(&tape).as_deref(), Some("KAT"));
+    assert_eq!(stroke("2026-10-16 10:00:00 Started logging"), None);
+    assert_eq!(stroke(""), None);
+    assert!(matches!(parse_line("Stroke(KQ : [])"), Some(Value::Invalid(_))));
+}
+
+#[test]
+fn follow_log() {
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("sdrill-strokes-{}.log", std::process::id()));
+    fs::write(&path, "2026-10-16 10:00:00.000 Stroke(TKOG : [])\n").unwrap();
+    let mut log = LogFollower::open(&path).unwrap();
+
+    // Only what is added after opening is read, and only once the line is complete.
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    write!(file, "2026-10-16 10:00:01.000 Stroke(KAT : [])\n2026-10-16 10:00:02.000 Str").unwrap();
+    log.read_new().unwrap();
+    assert_eq!(log.pending.len(), 1);
+    writeln!(file, "oke(* : [])").unwrap();
+    log.read_new().unwrap();
+    let strokes: Vec<_> = log
+        .pending
+        .drain(..)
+        .map(|value| match value {
+            Value::Stroke(stroke, _) => stroke.to_string(),
+            other => panic!("{:?}", other),
+        })
+        .collect();
+    assert_eq!(strokes, ["KAT", "*"]);
+
+    // A line can be cut off within a character, as when Plover logs a translation.
+    let text = "Translation(KAF/AEU : \"café\")\nStroke(-S : [])\n".as_bytes();
+    file.write_all(&text[..27]).unwrap();
+    log.read_new().unwrap();
+    file.write_all(&text[27..]).unwrap();
+    log.read_new().unwrap();
+    assert!(matches!(log.pending.pop_front(), Some(Value::Stroke(..))));
+    assert!(log.pending.is_empty());
+
+    // A new log is read from the start.
+    fs::write(&path, "Stroke(-T : [])\n").unwrap();
+    log.read_new().unwrap();
+    assert!(matches!(log.pending.pop_front(), Some(Value::Stroke(..))));
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Strokes given in advance, to drive a session in tests without anyone at the keyboard.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::{StrokeSource, Value};
+use crate::stroke::Stroke;
+
+pub struct Script {
+    values: VecDeque<Value>,
+}
+
+impl Script {
+    pub fn new<I: IntoIterator<Item = Value>>(values: I) -> Script {
+        Script {
+            values: values.into_iter().collect(),
+        }
+    }
+
+    /// A script of strokes, separated by spaces or slashes, such as "KAT / * / KAP".
+    pub fn parse(text: &str) -> Result<Script> {
+        let values = text
+            .split(|ch: char| ch == '/' || ch.is_whitespace())
+            .filter(|text| !text.is_empty())
+            .map(|text| Ok(Value::stroke(Stroke::from_text(text)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Script::new(values))
+    }
+}
+
+/// The values are given in order, without waiting, and then Exit.
+impl StrokeSource for Script {
+    fn read_stroke(&mut self, _timeout: Duration) -> Result<Value> {
+        Ok(self.values.pop_front().unwrap_or(Value::Exit))
+    }
+}
@@ -0,0 +1,160 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Serial ports, as used by steno machines.
+//!
+//! Steno machines that don't pretend to be a keyboard send their strokes over a serial port,
+//! usually a USB one.  The port is set to raw mode, so that the bytes arrive as they were sent,
+//! and read a packet at a time by the protocols.
+
+use anyhow::{anyhow, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::Value;
+
+pub struct Serial {
+    file: File,
+}
+
+impl Serial {
+    /// Open the serial device at `path`, in raw mode, at the given speed, such as `libc::B9600`.
+    pub fn open<P: AsRef<Path>>(path: P, speed: libc::speed_t) -> Result<Serial> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        let fd = file.as_raw_fd();
+        // Safety: the termios is only used after tcgetattr has filled it in, and the fd stays open
+        // for the life of the file.
+        unsafe {
+            let mut tio: libc::termios = std::mem::zeroed();
+            check(libc::tcgetattr(fd, &mut tio))?;
+            libc::cfmakeraw(&mut tio);
+            check(libc::cfsetspeed(&mut tio, speed))?;
+            tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+            // Reads return whatever has arrived, with the waiting done by `read`.
+            tio.c_cc[libc::VMIN] = 0;
+            tio.c_cc[libc::VTIME] = 0;
+            check(libc::tcsetattr(fd, libc::TCSANOW, &tio))?;
+            // Anything that arrived before the port was set up may not be whole packets.
+            check(libc::tcflush(fd, libc::TCIFLUSH))?;
+        }
+        Ok(Serial { file })
+    }
+
+    /// Wait up to `timeout` for bytes to arrive, and add them to `buffer`.  Returns how many bytes
+    /// were read, which is 0 on a timeout.
+    pub fn read(&mut self, buffer: &mut Vec<u8>, timeout: Duration) -> Result<usize> {
+        read_ready(&mut self.file, buffer, timeout)
+    }
+}
+
+/// Wait up to `timeout` for bytes to arrive on `file`, and add them to `buffer`.  Returns how many
+/// bytes were read, which is 0 on a timeout.
+pub fn read_ready(file: &mut File, buffer: &mut Vec<u8>, timeout: Duration) -> Result<usize> {
+    let mut fds = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // Safety: a single pollfd is passed, which lives across the call.
+    let ready = unsafe { libc::poll(&mut fds, 1, timeout.as_millis() as libc::c_int) };
+    check(ready)?;
+    if ready == 0 {
+        return Ok(0);
+    }
+    if fds.revents & (libc::POLLHUP | libc::POLLERR) != 0 && fds.revents & libc::POLLIN == 0 {
+        return Err(anyhow!("The input was disconnected"));
+    }
+
+    let mut bytes = [0u8; 64];
+    let count = file.read(&mut bytes)?;
+    buffer.extend_from_slice(&bytes[..count]);
+    Ok(count)
+}
+
+/// Read a stroke from a machine, with `read_packet` waiting up to the time it is given for the
+/// next one.  The keyboard is watched as well, so that Escape exits, and whatever the machine
+/// types, if it is also acting as a keyboard, is ignored.
+pub fn read_machine<F>(timeout: Duration, mut read_packet: F) -> Result<Value>
+where
+    F: FnMut(Duration) -> Result<Option<Value>>,
+{
+    let start = Instant::now();
+    loop {
+        let wait = timeout.saturating_sub(start.elapsed());
+        if let Some(value) = read_packet(wait.min(Duration::from_millis(50)))? {
+            return Ok(value);
+        }
+
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                }) => return Ok(Value::Exit),
+                Event::Resize(x, y) => return Ok(Value::Resize(x, y)),
+                _ => (),
+            }
+        }
+
+        if start.elapsed() >= timeout {
+            return Ok(Value::Timeout);
+        }
+    }
+}
+
+// Turn the result of a libc call into an error.
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Open a pseudo-terminal, to stand in for a steno machine in tests.  Returns the machine's end,
+/// to write the packets to, and the path of the port to read them from.
+#[cfg(test)]
+pub fn pty_pair() -> (File, std::path::PathBuf) {
+    use std::ffi::CStr;
+    use std::os::unix::io::FromRawFd;
+
+    // Safety: the fd is checked before it is given to the File, and the name is copied out of the
+    // buffer it was written into.
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        check(fd).unwrap();
+        let machine = File::from_raw_fd(fd);
+        check(libc::grantpt(fd)).unwrap();
+        check(libc::unlockpt(fd)).unwrap();
+        let mut name = [0 as libc::c_char; 64];
+        assert_eq!(libc::ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+        let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().into();
+        (machine, path)
+    }
+}
+
+#[test]
+fn serial_pty() {
+    use std::io::Write;
+
+    let (mut machine, path) = pty_pair();
+    let mut port = Serial::open(&path, libc::B9600).unwrap();
+    let mut buffer = vec![];
+    assert_eq!(port.read(&mut buffer, Duration::from_millis(10)).unwrap(), 0);
+
+    // Raw mode leaves bytes that a terminal would interpret alone.
+    machine.write_all(&[0x80, 0x03, 0x0d, 0x7f]).unwrap();
+    while buffer.len() < 4 {
+        port.read(&mut buffer, Duration::from_secs(1)).unwrap();
+    }
+    assert_eq!(buffer, [0x80, 0x03, 0x0d, 0x7f]);
+}
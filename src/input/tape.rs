@@ -0,0 +1,156 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Replaying the tape written during an earlier session.
+//!
+//! Each line of the tape is a stroke, as it would be shown on a paper tape, followed by the time,
+//! in seconds, since the session started.  The date written when a session starts, or any other
+//! line that isn't a stroke, separates sessions.  Tapes written before the times were added are
+//! replayed without waiting between strokes.
+//!
+//! The strokes are given the times they were written at, relative to the start of the replay, so
+//! that the time taken to write each word is the same as it was, even when replaying as fast as
+//! possible.  The first stroke of each session is given a short time after the stroke before it,
+//! so the times keep going forward from one session to the next.
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::{StrokeSource, Value};
+use crate::db::get_now;
+use crate::stroke::Stroke;
+
+// The time, in seconds, given to a stroke that the tape has no time for, after the stroke before.
+const GAP: f64 = 1.0;
+
+pub struct TapeReplay {
+    // The strokes, with the time since the stroke before, when it is known.
+    strokes: VecDeque<(Stroke, Option<f64>)>,
+    // Wait between strokes, as they were written, rather than replaying as fast as possible.
+    timing: bool,
+    // When the last stroke was given, and the time it was written at.
+    last: Instant,
+    written: f64,
+}
+
+impl TapeReplay {
+    pub fn load<P: AsRef<Path>>(path: P, timing: bool) -> Result<TapeReplay> {
+        let text = fs::read_to_string(path)?;
+        Ok(TapeReplay::parse(&text, timing))
+    }
+
+    pub fn parse(text: &str, timing: bool) -> TapeReplay {
+        let width = Stroke::tape_width();
+        let mut strokes = VecDeque::new();
+        let mut prev = None;
+        for line in text.lines() {
+            let split = line.char_indices().nth(width).map_or(line.len(), |(pos, _)| pos);
+            let (tape, time) = line.split_at(split);
+            let stroke = match Stroke::from_tape(tape) {
+                Some(stroke) => stroke,
+                None => {
+                    prev = None;
+                    continue;
+                }
+            };
+            let time = time.trim().parse::<f64>().ok();
+            let delay = match (prev, time) {
+                (Some(prev), Some(time)) => Some(f64::max(time - prev, 0.0)),
+                _ => None,
+            };
+            prev = time;
+            strokes.push_back((stroke, delay));
+        }
+        TapeReplay {
+            strokes,
+            timing,
+            last: Instant::now(),
+            written: get_now(),
+        }
+    }
+}
+
+/// When replaying with the original timing, the keyboard is watched while waiting, so that
+/// Escape can stop the replay.
+impl StrokeSource for TapeReplay {
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        let (stroke, delay) = match self.strokes.front() {
+            Some(&next) => next,
+            None => return Ok(Value::Exit),
+        };
+
+        if let (true, Some(delay)) = (self.timing, delay) {
+            let wait = Duration::from_secs_f64(delay).saturating_sub(self.last.elapsed());
+            if event::poll(wait.min(timeout))? {
+                return match event::read()? {
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Esc, ..
+                    }) => Ok(Value::Exit),
+                    Event::Resize(x, y) => Ok(Value::Resize(x, y)),
+                    _ => Ok(Value::Timeout),
+                };
+            }
+            if wait > timeout {
+                return Ok(Value::Timeout);
+            }
+        }
+
+        self.strokes.pop_front();
+        self.last = Instant::now();
+        self.written += delay.unwrap_or(GAP);
+        Ok(Value::Stroke(stroke, self.written))
+    }
+}
+
+#[test]
+fn replay_tape() {
+    let session = |tape: &mut String, date: &str, strokes: &[(&str, &str)]| {
+        tape.push_str(&format!("2026-10-{} 10:00:00.000000000 -06:00\n", date));
+        for (stroke, time) in strokes {
+            let stroke = Stroke::from_text(stroke).unwrap();
+            tape.push_str(format!("{} {}", stroke.to_tape(), time).trim_end());
+            tape.push('\n');
+        }
+    };
+    let mut tape = String::new();
+    session(&mut tape, "16", &[("KAT", "1.500"), ("*", "2.000"), ("KAP", "2.750")]);
+    // A later session, whose times start again from zero.
+    session(&mut tape, "17", &[("TKOG", "0.250"), ("TKOGS", "0.750")]);
+    // An older session, without times.
+    session(&mut tape, "18", &[("1-9", "")]);
+
+    let replay = TapeReplay::parse(&tape, false);
+    let strokes: Vec<_> = replay
+        .strokes
+        .iter()
+        .map(|(stroke, delay)| (stroke.to_string(), *delay))
+        .collect();
+    assert_eq!(
+        strokes,
+        [
+            ("KAT".to_string(), None),
+            ("*".to_string(), Some(0.5)),
+            ("KAP".to_string(), Some(0.75)),
+            ("TKOG".to_string(), None),
+            ("TKOGS".to_string(), Some(0.5)),
+            ("1-9".to_string(), None),
+        ]
+    );
+
+    let mut replay = replay;
+    let mut times = vec![];
+    for _ in 0..6 {
+        match replay.read_stroke(Duration::from_secs(1)).unwrap() {
+            Value::Stroke(_, time) => times.push(time),
+            other => panic!("{:?}", other),
+        }
+    }
+    // Replayed as fast as possible, the strokes are still as far apart as they were written, and
+    // the times keep going forward across sessions, though they run ahead of the clock.
+    assert!((times[2] - times[0] - 1.25).abs() < 1e-6);
+    assert!((times[4] - times[3] - 0.5).abs() < 1e-6);
+    assert!(times.windows(2).all(|pair| pair[1] > pair[0]));
+    assert!(matches!(replay.read_stroke(Duration::from_secs(1)).unwrap(), Value::Exit));
+}
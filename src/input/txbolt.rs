@@ -0,0 +1,208 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Reading strokes from a steno machine that speaks TX Bolt.
+//!
+//! TX Bolt is spoken by older machines, and by some firmware that offers it alongside Gemini PR.
+//! The keys are split into four banks, and a stroke is sent as one byte for each bank that has a
+//! key pressed, in order.  The top two bits of each byte give the bank, and the low six bits the
+//! keys in it, lowest bit first:
+//!
+//! ```text
+//! 0: S- T- K- P- W- H-
+//! 1: R- A- O- *  -E -U
+//! 2: -F -R -P -B -L -G
+//! 3: -T -S -D -Z #
+//! ```
+//!
+//! Nothing marks the end of a stroke, so a stroke ends when a byte for an earlier bank, or the
+//! same one, arrives, after the last bank, or when the machine goes quiet.  Some machines also
+//! send a zero byte after each stroke.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::serial::{self, Serial};
+use super::{StrokeSource, Value};
+use crate::stroke::{system, Stroke};
+
+/// The keys of each bank, in the order of their bits.
+pub static KEYS: [&str; 23] = [
+    "S-", "T-", "K-", "P-", "W-", "H-", //
+    "R-", "A-", "O-", "*", "-E", "-U", //
+    "-F", "-R", "-P", "-B", "-L", "-G", //
+    "-T", "-S", "-D", "-Z", "#", //
+];
+
+// How long the machine is quiet for before the stroke is taken to be complete.
+const QUIET: Duration = Duration::from_millis(20);
+
+/// Decodes a stream of TX Bolt bytes into strokes.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    // The keys of the stroke so far.
+    keys: Stroke,
+    // The bank of the last byte of the stroke, or None at the start of a stroke.
+    bank: Option<u8>,
+    // Strokes that are complete, but haven't been taken.
+    ready: VecDeque<Stroke>,
+}
+
+impl Decoder {
+    /// Add a byte from the machine.
+    pub fn push(&mut self, byte: u8) {
+        if byte == 0 {
+            self.end();
+            return;
+        }
+
+        let bank = byte >> 6;
+        if matches!(self.bank, Some(last) if bank <= last) {
+            self.end();
+        }
+
+        let system = system::current();
+        for bit in 0..6 {
+            if byte & (1 << bit) != 0 {
+                let key = KEYS.get(bank as usize * 6 + bit).and_then(|name| system.key_named(name));
+                if let Some(key) = key {
+                    self.keys = self.keys.union(key);
+                }
+            }
+        }
+        self.bank = Some(bank);
+
+        // Nothing can follow the last bank.
+        if bank == 3 {
+            self.end();
+        }
+    }
+
+    /// End the stroke, as when the machine goes quiet.  Strokes without any keys the system has
+    /// are dropped.
+    pub fn end(&mut self) {
+        let keys = std::mem::take(&mut self.keys);
+        if self.bank.take().is_some() && keys != Stroke::default() {
+            self.ready.push_back(keys);
+        }
+    }
+
+    /// Take the next complete stroke.
+    pub fn next_stroke(&mut self) -> Option<Stroke> {
+        self.ready.pop_front()
+    }
+
+    // Is a stroke partly received?
+    fn in_stroke(&self) -> bool {
+        self.bank.is_some()
+    }
+}
+
+pub struct TxBoltReader {
+    port: Serial,
+    decoder: Decoder,
+    // Bytes that have arrived, but haven't been decoded.
+    buffer: Vec<u8>,
+}
+
+impl TxBoltReader {
+    /// Read strokes from the machine on the serial port at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TxBoltReader> {
+        Ok(TxBoltReader {
+            port: Serial::open(path, libc::B9600)?,
+            decoder: Decoder::default(),
+            buffer: vec![],
+        })
+    }
+
+    // Wait up to `timeout` for a stroke from the machine.
+    fn read_packet(&mut self, timeout: Duration) -> Result<Option<Value>> {
+        let start = Instant::now();
+        loop {
+            if let Some(stroke) = self.decoder.next_stroke() {
+                return Ok(Some(Value::stroke(stroke)));
+            }
+
+            // Once a stroke has started, only wait long enough to see if there is more of it.
+            let mut wait = timeout.saturating_sub(start.elapsed());
+            if self.decoder.in_stroke() {
+                wait = wait.min(QUIET);
+            }
+            if self.port.read(&mut self.buffer, wait)? == 0 {
+                if !self.decoder.in_stroke() || wait < QUIET {
+                    return Ok(None);
+                }
+                self.decoder.end();
+                continue;
+            }
+
+            for byte in self.buffer.drain(..) {
+                self.decoder.push(byte);
+            }
+        }
+    }
+}
+
+impl StrokeSource for TxBoltReader {
+    /// Read a stroke from the machine.  Escape on the keyboard exits.
+    fn read_stroke(&mut self, timeout: Duration) -> Result<Value> {
+        serial::read_machine(timeout, |wait| self.read_packet(wait))
+    }
+}
+
+// Decode a stream of bytes, as captured from a machine.
+#[cfg(test)]
+fn decode(bytes: &[u8]) -> Vec<String> {
+    let mut decoder = Decoder::default();
+    for &byte in bytes {
+        decoder.push(byte);
+    }
+    decoder.end();
+    std::iter::from_fn(|| decoder.next_stroke()).map(|stroke| stroke.to_string()).collect()
+}
+
+#[test]
+fn txbolt_streams() {
+    // KAT, TKOG, and STPH-FPLT, each ending on a later bank.
+    assert_eq!(decode(&[0x04, 0x42, 0xc1]), ["KAT"]);
+    assert_eq!(decode(&[0x04, 0x42, 0xc1, 0x06, 0x44, 0xa0]), ["KAT", "TKOG"]);
+    assert_eq!(
+        decode(&[0x04, 0x42, 0xc1, 0x06, 0x44, 0xa0, 0x2b, 0x95, 0xc1]),
+        ["KAT", "TKOG", "STPH-FPLT"]
+    );
+
+    // Strokes that end on an earlier bank are split when the next one starts.
+    assert_eq!(decode(&[0x41, 0x02, 0x08]), ["R", "T", "P"]);
+    assert_eq!(decode(&[0x48, 0x48]), ["*", "*"]);
+    assert_eq!(decode(&[0xc1, 0xc2]), ["-T", "-S"]);
+
+    // Machines that end each stroke with a zero byte.
+    assert_eq!(decode(&[0x41, 0x00, 0x41, 0x00, 0x00]), ["R", "R"]);
+
+    // The number bar, and a byte with only keys the system doesn't have.
+    assert_eq!(decode(&[0x01, 0xc1 | 0x10]), ["1-9"]);
+    assert_eq!(decode(&[0xe0]), Vec::<String>::new());
+}
+
+#[test]
+fn txbolt_pty() {
+    use std::io::Write;
+
+    let (mut machine, path) = serial::pty_pair();
+    let mut reader = TxBoltReader::open(&path).unwrap();
+
+    let mut read = |bytes: &[u8]| {
+        machine.write_all(bytes).unwrap();
+        match reader.read_packet(Duration::from_secs(1)).unwrap() {
+            Some(Value::Stroke(stroke, _)) => stroke.to_string(),
+            other => format!("{:?}", other),
+        }
+    };
+
+    assert_eq!(read(&[0x04, 0x42, 0xc1]), "KAT");
+    // The end of a stroke that doesn't reach the last bank is seen when the machine goes quiet.
+    assert_eq!(read(&[0x06, 0x44, 0xa0]), "TKOG");
+    assert_eq!(read(&[0x48, 0x48]), "*");
+    assert_eq!(read(&[]), "*");
+    assert_eq!(read(&[]), "None");
+}
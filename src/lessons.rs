@@ -1,63 +1,430 @@
 // SPDX-License-Identifier: GPL-3.0
 //! Processing of lessons.
+//!
+//! Lessons can be read in a couple of formats.  The Typey-Type lesson.txt format has a description
+//! line, a blank line, and then entries of the form `'word': STENO`.  The TSV format, used by the
+//! lapwing lessons, has no header, and each line is `word<TAB>STENO`.  As the TSV files have no
+//! description, the list name is taken from the name of the file.
+//!
+//! In both of these formats, the STENO can be followed by alternate outlines that will also be
+//! accepted, separated by '|', for example `'because': PWAUZ | PWUZ`.  The first is the canonical
+//! outline, and the others are shown after it in the hint.
+//!
+//! The outlines can also be followed by up to three more tab separated fields: a label to show
+//! instead of the word, a note (such as a mnemonic) shown along with the hint, and a comma
+//! separated list of tags.  Any of these can be left empty.  For example, the label can tell
+//! apart symbols that are written the same, without changing the word itself:
+//! `'"': KW-GS<TAB>" (open)<TAB>KW for quote, GS for start<TAB>punctuation`.
+//!
+//! Plover JSON dictionaries (`{"STROKE/STROKE": "translation"}`) can also be read.  These are
+//! inverted, giving one entry per translation.  When a translation has several outlines, the one
+//! with the fewest strokes is the canonical outline, and the others are accepted as alternates.
+//! As dictionaries are usually large, a [`Filter`] can be used to select just part of one,
+//! allowing a single dictionary to be imported as many lists.
 
-use crate::stroke::StenoPhrase;
+use crate::stroke::{Mode, StenoPhrase, Stroke};
 use anyhow::{anyhow, bail, Result};
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{BufRead, BufReader, Read, Write},
     path::Path,
+    str::FromStr,
 };
 
 #[derive(Debug)]
 pub struct Entry {
     pub word: String,
     pub steno: StenoPhrase,
+    /// Other outlines that are also accepted for this word.
+    pub alternates: Vec<StenoPhrase>,
+    /// The line of the lesson file this entry came from, or 0 if it didn't come from a line.
+    pub line: usize,
+    /// Text to show instead of the word, when the word alone is ambiguous.
+    pub label: Option<String>,
+    /// A note about the word, such as a mnemonic for the outline.
+    pub note: Option<String>,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct Lesson {
     pub description: String,
     pub entries: Vec<Entry>,
+    /// Problems found in the lesson that didn't prevent it from loading, each prefixed with where
+    /// they were found.
+    pub warnings: Vec<String>,
+}
+
+/// The formats lesson files can be stored in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Typey-Type's lesson.txt: description, blank line, then `'word': STENO` entries.
+    TypeyType,
+    /// Tab separated: `word<TAB>STENO`, with no header.
+    Tsv,
+    /// A Plover JSON dictionary.
+    Plover,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "typey" | "typey-type" | "txt" => Ok(Format::TypeyType),
+            "tsv" => Ok(Format::Tsv),
+            "plover" | "json" => Ok(Format::Plover),
+            _ => bail!("Unknown lesson format: {:?} (expecting typey, tsv or plover)", s),
+        }
+    }
+}
+
+impl Format {
+    /// Try to determine the format of a lesson from its first line.  Typey-Type lessons start
+    /// with a description, which will never contain a tab, whereas every line of a TSV lesson
+    /// does.  A JSON dictionary will start with a brace.
+    pub fn detect(first: &str) -> Format {
+        if first.trim_start().starts_with('{') {
+            Format::Plover
+        } else if first.contains('\t') {
+            Format::Tsv
+        } else {
+            Format::TypeyType
+        }
+    }
+}
+
+/// Restrictions on which entries are taken from a lesson.
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// Only accept outlines that use no keys other than these.
+    pub keys: Option<Stroke>,
+    /// Only accept outlines with at most this many strokes.
+    pub max_strokes: Option<usize>,
+    /// Only accept these words.  The entries will also be placed in the order of this list.
+    pub words: Option<Vec<String>>,
+}
+
+impl Filter {
+    /// Does this entry pass the filter?
+    fn accepts(&self, word: &str, steno: &StenoPhrase) -> bool {
+        if let Some(words) = &self.words {
+            if !words.iter().any(|w| w == word) {
+                return false;
+            }
+        }
+        self.accepts_outline(steno)
+    }
+
+    /// Does this outline pass the filter?  Used to filter alternates.
+    fn accepts_outline(&self, steno: &StenoPhrase) -> bool {
+        let strokes = steno.linear();
+        if let Some(keys) = self.keys {
+            if !strokes.iter().all(|st| st.is_subset(keys)) {
+                return false;
+            }
+        }
+        if let Some(max) = self.max_strokes {
+            if strokes.len() > max {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Read a list of words, one per line, to be used as the word filter.
+    pub fn load_words<P: AsRef<Path>>(path: P) -> Result<Vec<String>> {
+        let mut words = vec![];
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let line = line.trim();
+            if !line.is_empty() {
+                words.push(line.to_string());
+            }
+        }
+        Ok(words)
+    }
+
+    /// Reorder the entries to match the order of the word list, if there is one.
+    fn reorder(&self, entries: &mut [Entry]) {
+        if let Some(words) = &self.words {
+            let order: HashMap<&str, usize> = words
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(pos, w)| (w.as_str(), pos))
+                .collect();
+            entries.sort_by_key(|e| order[e.word.as_str()]);
+        }
+    }
 }
 
 impl Lesson {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Lesson> {
-        let mut inp = BufReader::new(File::open(path)?).lines();
+    /// Load a lesson.  If `format` is None, the format will be detected from the contents of the
+    /// file.  The `name`, if given, overrides the description of the lesson, which becomes the
+    /// name of the list when imported.  Only entries accepted by `filter` are kept.  All entries
+    /// are checked, and any that fail to parse are reported, with their line numbers, before
+    /// returning an error.  In strict mode, questionable strokes are errors rather than warnings.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        format: Option<Format>,
+        name: Option<&str>,
+        filter: &Filter,
+        mode: Mode,
+    ) -> Result<Lesson> {
+        let path = path.as_ref();
+        let (lesson, errors) = Lesson::read(path, format, name, filter, mode)?;
+        for error in &errors {
+            println!("{}", error);
+        }
+        if !errors.is_empty() {
+            bail!("{} errors in lesson file {}", errors.len(), path.display());
+        }
+
+        println!("Description: {}", lesson.description);
+
+        Ok(lesson)
+    }
+
+    /// Read a lesson, as with `load`, but return the entries that could be read along with the
+    /// errors for those that couldn't, rather than failing.  Only a file that can't be read at
+    /// all is an error.
+    pub fn read<P: AsRef<Path>>(
+        path: P,
+        format: Option<Format>,
+        name: Option<&str>,
+        filter: &Filter,
+        mode: Mode,
+    ) -> Result<(Lesson, Vec<String>)> {
+        let path = path.as_ref();
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
 
-        let description = oneline(&mut inp)?;
-        let blank = oneline(&mut inp)?;
-        if !blank.is_empty() {
-            bail!("Expecting lesson file to have a blank second line");
+        let format = match format {
+            Some(format) => format,
+            None => Format::detect(text.lines().next().unwrap_or("")),
+        };
+
+        let (mut lesson, errors) = match format {
+            Format::Plover => Lesson::load_plover(path, &text, filter, mode)?,
+            _ => Lesson::load_lines(path, format, &text, filter, mode)?,
+        };
+        filter.reorder(&mut lesson.entries);
+        if let Some(name) = name {
+            lesson.description = name.to_string();
         }
 
-        println!("Description: {}", description);
+        Ok((lesson, errors))
+    }
+
+    // Load one of the line oriented lesson formats, returning the lesson and its errors.
+    fn load_lines(
+        path: &Path,
+        format: Format,
+        text: &str,
+        filter: &Filter,
+        mode: Mode,
+    ) -> Result<(Lesson, Vec<String>)> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(num, line)| (num + 1, line.to_string()));
+
+        let description = match format {
+            Format::TypeyType => {
+                let description = oneline(&mut lines)?;
+                let blank = oneline(&mut lines)?;
+                if !blank.is_empty() {
+                    bail!("Expecting lesson file to have a blank second line");
+                }
+                description
+            }
+            _ => file_name(path)?,
+        };
 
         let mut entries = vec![];
+        let mut warnings = vec![];
+        let mut errors = vec![];
 
-        for line in inp {
-            let line = line?;
-            if let Some(entry) = Entry::parse(&line)? {
-                entries.push(entry);
-            } else {
-                println!("  {}", line);
+        for (num, line) in lines {
+            let mut line_warnings = vec![];
+            let entry = match format {
+                Format::Tsv => Entry::parse_tsv(&line, mode, &mut line_warnings),
+                _ => Entry::parse(&line, mode, &mut line_warnings),
+            };
+            match entry {
+                Ok(Some(mut entry)) => {
+                    if filter.accepts(&entry.word, &entry.steno) {
+                        entry.alternates.retain(|alt| filter.accepts_outline(alt));
+                        entry.line = num;
+                        entries.push(entry);
+                    }
+                }
+                // A line with a colon that isn't an entry is likely a mistake in the word's
+                // quoting.
+                Ok(None) if format == Format::TypeyType && line.contains(": ") => {
+                    line_warnings.push(format!("skipped, word is not quoted: {}", line));
+                }
+                Ok(None) if line.trim().is_empty() => (),
+                Ok(None) => println!("  {}", line),
+                Err(e) => errors.push(format!("{}:{}: {}", path.display(), num, e)),
+            }
+            for warning in line_warnings {
+                warnings.push(format!("{}:{}: {}", path.display(), num, warning));
             }
         }
 
-        Ok(Lesson {
+        let lesson = Lesson {
             description,
             entries,
-        })
+            warnings,
+        };
+        Ok((lesson, errors))
+    }
+
+    // Load a Plover dictionary, inverting it so that each translation becomes an entry.  The
+    // entries are kept in the order of the dictionary.  Real dictionaries always have some entries
+    // that can't be used, so these are skipped with a warning, unless in strict mode.
+    fn load_plover(
+        path: &Path,
+        text: &str,
+        filter: &Filter,
+        mode: Mode,
+    ) -> Result<(Lesson, Vec<String>)> {
+        let dict: serde_json::Map<String, serde_json::Value> = serde_json::from_str(text)?;
+
+        let mut entries: Vec<Entry> = vec![];
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut warnings = vec![];
+        let mut errors = vec![];
+
+        let mut skip = |problem: String, warnings: &mut Vec<String>| {
+            if mode == Mode::Strict {
+                errors.push(format!("{}: {}", path.display(), problem));
+            } else {
+                warnings.push(format!("{}: skipped, {}", path.display(), problem));
+            }
+        };
+
+        for (steno_text, word) in dict {
+            let word = match word {
+                serde_json::Value::String(word) => word,
+                _ => {
+                    skip(format!("{:?}: translation is not a string", steno_text), &mut warnings);
+                    continue;
+                }
+            };
+            let mut steno_warnings = vec![];
+            let steno = StenoPhrase::parse_checked(&steno_text, mode, &mut steno_warnings);
+            for warning in steno_warnings {
+                warnings.push(format!("{}: {}", path.display(), warning));
+            }
+            let steno = match steno {
+                Ok(steno) => steno,
+                Err(e) => {
+                    skip(e.to_string(), &mut warnings);
+                    continue;
+                }
+            };
+            if !filter.accepts(&word, &steno) {
+                continue;
+            }
+
+            match index.get(&word) {
+                Some(&pos) => {
+                    let entry = &mut entries[pos];
+                    if steno.linear().len() < entry.steno.linear().len() {
+                        let old = std::mem::replace(&mut entry.steno, steno);
+                        entry.alternates.push(old);
+                    } else {
+                        entry.alternates.push(steno);
+                    }
+                }
+                None => {
+                    index.insert(word.clone(), entries.len());
+                    entries.push(Entry::new(word, steno, vec![]));
+                }
+            }
+        }
+
+        let lesson = Lesson {
+            description: file_name(path)?,
+            entries,
+            warnings,
+        };
+        Ok((lesson, errors))
+    }
+}
+
+impl Lesson {
+    /// Write the lesson out in the given format.  This is the inverse of `load`, so a lesson can
+    /// be exported and imported again.  In a Plover dictionary, the alternates are written as
+    /// additional entries for the same word.
+    pub fn write<W: Write>(&self, out: &mut W, format: Format) -> Result<()> {
+        match format {
+            Format::TypeyType => {
+                writeln!(out, "{}", self.description)?;
+                writeln!(out)?;
+                for entry in &self.entries {
+                    writeln!(out, "'{}': {}{}", entry.word, entry.outlines(), entry.annotations())?;
+                }
+            }
+            Format::Tsv => {
+                for entry in &self.entries {
+                    writeln!(out, "{}\t{}{}", entry.word, entry.outlines(), entry.annotations())?;
+                }
+            }
+            Format::Plover => {
+                let mut dict = serde_json::Map::new();
+                for entry in &self.entries {
+                    for steno in std::iter::once(&entry.steno).chain(&entry.alternates) {
+                        let steno = steno.to_string();
+                        // The dictionary may be going to stdout, so the warning goes elsewhere.
+                        if let Some(old) = dict.get(&steno) {
+                            eprintln!(
+                                "warning: {} is used for both {} and {:?}, keeping the latter",
+                                steno, old, entry.word
+                            );
+                        }
+                        dict.insert(steno, serde_json::Value::String(entry.word.clone()));
+                    }
+                }
+                serde_json::to_writer_pretty(&mut *out, &dict)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
     }
 }
 
 impl Entry {
+    pub fn new(word: String, steno: StenoPhrase, alternates: Vec<StenoPhrase>) -> Entry {
+        Entry {
+            word,
+            steno,
+            alternates,
+            line: 0,
+            label: None,
+            note: None,
+            tags: vec![],
+        }
+    }
+
+    // The outlines of this entry as written in a lesson file: the canonical outline followed by
+    // the alternates.
+    fn outlines(&self) -> String {
+        let mut outlines = vec![self.steno.clone()];
+        outlines.extend(self.alternates.iter().cloned());
+        StenoPhrase::join_list(&outlines)
+    }
+
     // Parse this line as an entry.  Can return Ok(None) if this line doesn't start with a '\''
     // character, or have a colon.  May return an error if there was a problem decoding the line.
     // Entries are expected to have the format:
     // 'text': STENO
-    // where text is an _arbitrary_ string (which may include single quotes".
-    fn parse(text: &str) -> Result<Option<Entry>> {
+    // where text is an _arbitrary_ string (which may include single quotes".  STENO may be
+    // followed by alternates, separated by '|'.  Questionable strokes are added to `warnings`.
+    fn parse(text: &str, mode: Mode, warnings: &mut Vec<String>) -> Result<Option<Entry>> {
         let fields: Vec<_> = text.splitn(2, ": ").collect();
         if fields.len() != 2 {
             return Ok(None);
@@ -66,25 +433,228 @@ impl Entry {
         let word = fields[0];
         if word.len() < 2 || !word.starts_with('\'') || !word.ends_with('\'') {
             // If the extra data has a colon in it, it will trigger this.
-            println!("warning: {}: {}", word, fields[1]);
             return Ok(None);
             // bail!("Looks like entry, but word is not surrounded by ''");
         }
         let word = &word[1..word.len() - 1];
         let word = word.to_string();
 
-        let steno = StenoPhrase::parse(fields[1])?;
+        let fields: Vec<_> = fields[1].split('\t').collect();
+        let (steno, alternates) = parse_outlines(fields[0], mode, warnings)?;
+
+        let mut entry = Entry::new(word, steno, alternates);
+        entry.annotate(&fields[1..])?;
+        Ok(Some(entry))
+    }
+
+    // Parse a line from a tab separated lesson.  Blank lines are skipped, returning Ok(None).
+    // Entries have the format:
+    // text<TAB>STENO
+    fn parse_tsv(text: &str, mode: Mode, warnings: &mut Vec<String>) -> Result<Option<Entry>> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let fields: Vec<_> = text.split('\t').collect();
+        if fields.len() < 2 {
+            bail!("Expecting at least 2 tab separated fields, found {}", fields.len());
+        }
+        if fields[0].is_empty() {
+            bail!("Entry has an empty word");
+        }
+
+        let word = fields[0].to_string();
+        let (steno, alternates) = parse_outlines(fields[1], mode, warnings)?;
 
-        Ok(Some(Entry { word, steno }))
+        let mut entry = Entry::new(word, steno, alternates);
+        entry.annotate(&fields[2..])?;
+        Ok(Some(entry))
+    }
+
+    // Set the label, note and tags from the fields following the outlines.  Empty fields are
+    // left unset.
+    fn annotate(&mut self, fields: &[&str]) -> Result<()> {
+        if fields.len() > 3 {
+            bail!("Too many fields, expecting at most label, note and tags after the outlines");
+        }
+        let field = |n: usize| {
+            fields
+                .get(n)
+                .map(|f| f.trim())
+                .filter(|f| !f.is_empty())
+                .map(|f| f.to_string())
+        };
+        self.label = field(0);
+        self.note = field(1);
+        self.tags = Entry::parse_tags(fields.get(2).copied().unwrap_or(""));
+        Ok(())
     }
+
+    /// Split a comma separated list of tags.
+    pub fn parse_tags(text: &str) -> Vec<String> {
+        text.split(',')
+            .map(|tag| tag.trim())
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| tag.to_string())
+            .collect()
+    }
+
+    // The fields following the outlines when written to a lesson file, each preceded by a tab.
+    // Trailing empty fields are left off.
+    fn annotations(&self) -> String {
+        let mut fields = vec![
+            self.label.clone().unwrap_or_default(),
+            self.note.clone().unwrap_or_default(),
+            self.tags.join(","),
+        ];
+        while fields.last().is_some_and(|f| f.is_empty()) {
+            fields.pop();
+        }
+        fields.iter().map(|f| format!("\t{}", f)).collect()
+    }
+}
+
+// Parse the outlines of an entry, the canonical outline followed by any alternates.
+fn parse_outlines(
+    text: &str,
+    mode: Mode,
+    warnings: &mut Vec<String>,
+) -> Result<(StenoPhrase, Vec<StenoPhrase>)> {
+    let mut diagnostics = vec![];
+    let outlines = StenoPhrase::parse_list_checked(text, mode, &mut diagnostics);
+    warnings.extend(diagnostics.iter().map(|d| d.to_string()));
+    let mut outlines = outlines?.into_iter();
+    let steno = outlines
+        .next()
+        .ok_or_else(|| anyhow!("Entry has no outline"))?;
+    Ok((steno, outlines.collect()))
+}
+
+// The name of a lesson file, without the directory or extension.
+fn file_name(path: &Path) -> Result<String> {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("Unable to derive list name from {:?}", path))
 }
 
 // Read a single line from the reader, returning an error if we've reached the end.
-fn oneline<B>(rd: &mut io::Lines<B>) -> Result<String>
+fn oneline<I>(rd: &mut I) -> Result<String>
 where
-    B: BufRead,
+    I: Iterator<Item = (usize, String)>,
 {
     Ok(rd
         .next()
-        .ok_or_else(|| anyhow!("Unexpected EOF on lesson file"))??)
+        .ok_or_else(|| anyhow!("Unexpected EOF on lesson file"))?
+        .1)
+}
+
+#[test]
+fn tsv_entries() {
+    assert_eq!(Format::detect("rad\tRAD"), Format::Tsv);
+    assert_eq!(Format::detect("Introduction"), Format::TypeyType);
+
+    let parse = |text: &str, warnings: &mut Vec<String>| {
+        Entry::parse_tsv(text, Mode::Lenient, warnings)
+    };
+
+    let entry = parse("sentence\tSTEPBS", &mut vec![]).unwrap().unwrap();
+    assert_eq!(entry.word, "sentence");
+    assert_eq!(entry.steno.to_string(), "STEPBS");
+
+    let entry = parse("because\tPWAUZ | PWUZ", &mut vec![]).unwrap().unwrap();
+    assert_eq!(entry.steno.to_string(), "PWAUZ");
+    assert_eq!(StenoPhrase::join_list(&entry.alternates), "PWUZ");
+
+    let mut warnings = vec![];
+    assert!(parse("", &mut warnings).unwrap().is_none());
+    assert!(parse("word only", &mut warnings).is_err());
+    assert!(parse("bad\tSTKQ", &mut warnings).is_err());
+    assert!(warnings.is_empty());
+    assert!(parse("ten\tTEPB | #S-Z", &mut warnings).unwrap().is_some());
+    assert_eq!(warnings, [r#""TEPB | #S-Z", column 9: 'S' is used with '#', which makes it '1'"#]);
+    assert!(Entry::parse_tsv("ten\t#S-Z", Mode::Strict, &mut vec![]).is_err());
+
+    let entry = parse("\"\tKW-GS\t\" (open)\t\tpunct, symbol", &mut vec![])
+        .unwrap()
+        .unwrap();
+    assert_eq!(entry.label.as_deref(), Some("\" (open)"));
+    assert_eq!(entry.note, None);
+    assert_eq!(entry.tags, ["punct", "symbol"]);
+    assert!(parse("a\tA\tb\tc\td\te", &mut vec![]).is_err());
+}
+
+#[test]
+fn plover_filter() {
+    let load = |dict: &str, filter: &Filter, mode: Mode| {
+        Lesson::load_plover(Path::new("dict.json"), dict, filter, mode).unwrap()
+    };
+    let dict = r#"{"KAT": "cat", "KA*T": "cat", "KAT/-S": "cats", "TKOG": "dog", "KAP": "cap"}"#;
+    let mut filter = Filter {
+        keys: Some(Stroke::from_text("TKPAOGT").unwrap()),
+        ..Filter::default()
+    };
+    let lesson = load(dict, &filter, Mode::Lenient).0;
+    let words: Vec<_> = lesson.entries.iter().map(|e| e.word.as_str()).collect();
+    assert_eq!(words, ["cat", "dog"]);
+    assert_eq!(lesson.entries[0].steno.to_string(), "KAT");
+    assert!(lesson.entries[0].alternates.is_empty());
+
+    filter.keys = None;
+    filter.max_strokes = Some(1);
+    filter.words = Some(vec!["cap".to_string(), "cats".to_string(), "cat".to_string()]);
+    let mut lesson = load(dict, &filter, Mode::Lenient).0;
+    filter.reorder(&mut lesson.entries);
+    let words: Vec<_> = lesson.entries.iter().map(|e| e.word.as_str()).collect();
+    assert_eq!(words, ["cap", "cat"]);
+    assert_eq!(StenoPhrase::join_list(&lesson.entries[1].alternates), "KA*T");
+
+    // Outlines that can't be parsed are skipped, unless in strict mode.
+    let dict = r#"{"KAT": "cat", "KQAT": "cat", "TKOG": 1}"#;
+    let filter = Filter::default();
+    let lesson = load(dict, &filter, Mode::Lenient).0;
+    assert_eq!(lesson.entries.len(), 1);
+    assert_eq!(lesson.warnings.len(), 2);
+    assert!(lesson.warnings.iter().all(|w| w.starts_with("dict.json: skipped, ")));
+    let (_, errors) = load(dict, &filter, Mode::Strict);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn write_roundtrip() {
+    let lesson = Lesson {
+        description: "Roundtrip".to_string(),
+        entries: vec![Entry {
+            label: Some("because (conj)".to_string()),
+            tags: vec!["brief".to_string(), "common".to_string()],
+            ..Entry::new(
+                "because".to_string(),
+                StenoPhrase::parse("PWAUZ").unwrap(),
+                vec![StenoPhrase::parse("PWUZ").unwrap()],
+            )
+        }],
+        warnings: vec![],
+    };
+
+    for format in [Format::TypeyType, Format::Tsv, Format::Plover] {
+        let mut text = vec![];
+        lesson.write(&mut text, format).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        let path = Path::new("roundtrip.txt");
+        let back = match format {
+            Format::Plover => {
+                Lesson::load_plover(path, &text, &Filter::default(), Mode::Lenient).unwrap().0
+            }
+            _ => Lesson::load_lines(path, format, &text, &Filter::default(), Mode::Lenient)
+                .unwrap()
+                .0,
+        };
+        assert_eq!(back.entries.len(), 1);
+        assert_eq!(back.entries[0].word, "because");
+        assert_eq!(back.entries[0].outlines(), "PWAUZ | PWUZ");
+        if format != Format::Plover {
+            assert_eq!(back.entries[0].label.as_deref(), Some("because (conj)"));
+            assert_eq!(back.entries[0].note, None);
+            assert_eq!(back.entries[0].tags, ["brief", "common"]);
+        }
+    }
 }
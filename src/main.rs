@@ -2,17 +2,32 @@
 //! Steno learning application.
 
 use chrono::Local;
-use crate::db::Db;
-use crate::lessons::Lesson;
-use crate::ui::{LearnApp, NewList, Ui};
-use anyhow::Result;
+use crate::course::Course;
+use crate::db::{Db, SyncMode};
+use crate::dict::Dictionary;
+use crate::errors::Accuracy;
+use crate::input::{
+    KeyboardMode, KeyboardSteno, Layout, LogFollower, StrokeReader, StrokeSource, TapeReplay,
+};
+#[cfg(unix)]
+use crate::input::{GeminiReader, TxBoltReader};
+use crate::lessons::{Filter, Format, Lesson};
+use crate::stroke::system::{self, System};
+use crate::stroke::{Mode, Stroke};
+use crate::ui::{LearnApp, NewList, StatsApp, Ui};
+use anyhow::{bail, Result};
 use log::info;
 use std::io::Write;
 use std::fs::File;
 use std::time::Duration;
 use structopt::StructOpt;
 
+mod check;
+mod course;
 mod db;
+mod dict;
+mod errors;
+mod hesitation;
 mod input;
 mod lessons;
 mod stroke;
@@ -43,6 +58,30 @@ enum Command {
     #[structopt(name = "tolearn")]
     /// Show a list of what is to be learned.
     ToLearn(ToLearnCommand),
+
+    #[structopt(name = "sync")]
+    /// Update words being learned whose outline has changed in the lessons
+    Sync(SyncCommand),
+
+    #[structopt(name = "migrate")]
+    /// Upgrade the database to the current schema
+    Migrate(MigrateCommand),
+
+    #[structopt(name = "check")]
+    /// Check lesson files for problems before importing them
+    Check(CheckCommand),
+
+    #[structopt(name = "export")]
+    /// Write a list back out as a lesson file
+    Export(ExportCommand),
+
+    #[structopt(name = "errors")]
+    /// Show which keys are most often wrong
+    Errors(ErrorsCommand),
+
+    #[structopt(name = "slowest")]
+    /// Show the words that are hesitated over the longest
+    Slowest(SlowestCommand),
 }
 
 #[derive(Debug, StructOpt)]
@@ -51,6 +90,42 @@ struct ImportCommand {
     /// The pathname of the learning database
     file: String,
 
+    #[structopt(long = "format")]
+    /// The format of the lesson files (typey, tsv or plover).  Detected from the contents if not
+    /// given.
+    format: Option<Format>,
+
+    #[structopt(long = "name")]
+    /// The name to give the imported list.  Only valid when importing a single file.
+    name: Option<String>,
+
+    #[structopt(long = "update")]
+    /// Update lists that have already been imported, matching them by name.  The list IDs and
+    /// learning progress are kept.
+    update: bool,
+
+    #[structopt(long = "keys")]
+    /// Only import outlines using just these keys, e.g. "STKPWHRAO*EU"
+    keys: Option<String>,
+
+    #[structopt(long = "max-strokes")]
+    /// Only import outlines of at most this many strokes
+    max_strokes: Option<usize>,
+
+    #[structopt(long = "words")]
+    /// Only import the words listed, one per line, in this file.  The list is imported in the
+    /// order of this file.
+    words: Option<String>,
+
+    #[structopt(long = "course")]
+    /// Import a whole course from a manifest, instead of individual files.  The lists are created
+    /// or updated, as with --update, and grouped into the chapters of the course.
+    course: Option<String>,
+
+    #[structopt(long = "strict")]
+    /// Treat questionable strokes, such as "#S" instead of "1", as errors instead of warnings
+    strict: bool,
+
     #[structopt(name = "FILE")]
     files: Vec<String>,
 }
@@ -60,6 +135,11 @@ struct InitCommand {
     #[structopt(long = "db")]
     /// The pathname of the learning database
     file: String,
+
+    #[structopt(long = "system", default_value = "english")]
+    /// The steno system the lessons are written in, either the name of a built-in system, or a
+    /// file defining one
+    system: String,
 }
 
 #[derive(Debug, StructOpt)]
@@ -79,6 +159,10 @@ struct InfoCommand {
     #[structopt(long = "hide-learned")]
     /// Hide entries that have been fully learned
     hide_learned: bool,
+
+    #[structopt(long = "tui")]
+    /// Show the accuracy of each key on a screen of its own, instead of the lesson progress
+    tui: bool,
 }
 
 #[derive(Debug, StructOpt)]
@@ -99,10 +183,23 @@ struct LearnCommand {
     /// Append strokes in tape format to given file
     tape_file: Option<String>,
 
+    #[structopt(flatten)]
+    input: InputArgs,
+
+    #[structopt(long = "dict")]
+    /// A Plover dictionary to show what the strokes would have written.  Can be given more than
+    /// once, with the first taking priority, as in Plover.
+    dicts: Vec<String>,
+
     #[structopt(long = "limit")]
     /// Limit the number of new words learned
     limit: Option<usize>,
 
+    #[structopt(long = "prefer-canonical")]
+    /// Accept alternate outlines, but note that they are not preferred, and keep the word at its
+    /// current interval rather than increasing it
+    prefer_canonical: bool,
+
     #[structopt(long = "tui")]
     /// Enable the TUI interface (deprecated)
     #[allow(dead_code)] // Deprecated: to be removed later
@@ -126,6 +223,57 @@ struct DrillCommand {
     #[structopt(long = "tape")]
     /// Append strokes in tape format to given file
     tape_file: Option<String>,
+
+    #[structopt(flatten)]
+    input: InputArgs,
+
+    #[structopt(long = "dict")]
+    /// A Plover dictionary to show what the strokes would have written.  Can be given more than
+    /// once, with the first taking priority, as in Plover.
+    dicts: Vec<String>,
+}
+
+/// Where the strokes are read from, when not from the keyboard.
+#[derive(Debug, StructOpt)]
+struct InputArgs {
+    #[structopt(long = "keyboard")]
+    /// Write steno on the keyboard, without Plover, either "arpeggiate", pressing the keys of each
+    /// stroke in turn and then space, or "chord", in terminals that report when keys are released
+    keyboard: Option<KeyboardMode>,
+
+    #[structopt(long = "layout")]
+    /// The keyboard layout for --keyboard, instead of Plover's QWERTY layout
+    layout: Option<String>,
+
+    #[structopt(long = "gemini")]
+    /// Read strokes from a steno machine that speaks Gemini PR on this serial port, such as
+    /// /dev/ttyACM0, instead of the keyboard
+    gemini: Option<String>,
+
+    #[structopt(long = "exit-key", default_value = "Fn")]
+    /// The key on a Gemini PR steno machine that exits, given by its Gemini PR name, such as "Fn",
+    /// "pwr" or "res1"
+    #[cfg_attr(not(unix), allow(dead_code))]
+    exit_key: String,
+
+    #[structopt(long = "txbolt")]
+    /// Read strokes from a steno machine that speaks TX Bolt on this serial port, instead of the
+    /// keyboard
+    txbolt: Option<String>,
+
+    #[structopt(long = "log")]
+    /// Read strokes from Plover's stroke log (strokes.log), or another file strokes are added to,
+    /// instead of the keyboard.  Plover can then be left set up for normal writing.
+    log: Option<String>,
+
+    #[structopt(long = "replay")]
+    /// Replay the strokes from a tape written with --tape, instead of reading the keyboard.  The
+    /// database isn't changed.
+    replay: Option<String>,
+
+    #[structopt(long = "fast")]
+    /// Replay the tape as fast as possible, rather than with the original timing
+    fast: bool,
 }
 
 #[derive(Debug, StructOpt)]
@@ -139,6 +287,109 @@ struct ToLearnCommand {
     limit: usize,
 }
 
+#[derive(Debug, StructOpt)]
+struct SyncCommand {
+    #[structopt(long = "db")]
+    /// The pathname of the learning database.
+    file: String,
+
+    #[structopt(long = "apply")]
+    /// Update the outlines, keeping the learning progress.  Without this, or one of the options
+    /// below, the differences are only shown.
+    apply: bool,
+
+    #[structopt(long = "review")]
+    /// Update the outlines, and make the words due now, so they are reviewed right away
+    review: bool,
+
+    #[structopt(long = "reset")]
+    /// Update the outlines, and reset the words so they are learned again
+    reset: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct MigrateCommand {
+    #[structopt(long = "db")]
+    /// The pathname of the learning database.
+    file: String,
+
+    #[structopt(long = "dry-run")]
+    /// Show the changes that would be made, without making them
+    dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+struct ExportCommand {
+    #[structopt(long = "db")]
+    /// The learning database
+    file: String,
+
+    #[structopt(long = "format", default_value = "typey")]
+    /// The format to write (typey, tsv or plover)
+    format: Format,
+
+    #[structopt(long = "output", short = "o")]
+    /// The file to write to, instead of stdout
+    output: Option<String>,
+
+    #[structopt(name = "LIST")]
+    /// The list to export, by id or name (as shown by `info`)
+    list: String,
+}
+
+#[derive(Debug, StructOpt)]
+struct ErrorsCommand {
+    #[structopt(long = "db")]
+    /// The pathname of the learning database
+    file: String,
+
+    #[structopt(long = "days", default_value = "30")]
+    /// Only include errors made in this many days
+    days: f64,
+
+    #[structopt(long = "top", default_value = "10")]
+    /// How many of the most frequent keys and pairs to show
+    top: usize,
+}
+
+#[derive(Debug, StructOpt)]
+struct SlowestCommand {
+    #[structopt(long = "db")]
+    /// The pathname of the learning database
+    file: String,
+
+    #[structopt(long = "days", default_value = "30")]
+    /// Only include words written in this many days
+    days: f64,
+
+    #[structopt(long = "top", default_value = "20")]
+    /// How many of the slowest words to show
+    top: usize,
+}
+
+#[derive(Debug, StructOpt)]
+struct CheckCommand {
+    #[structopt(long = "db")]
+    /// A learning database whose lists the files are also checked against
+    file: Option<String>,
+
+    #[structopt(long = "format")]
+    /// The format of the lesson files (typey, tsv or plover).  Detected from the contents if not
+    /// given.
+    format: Option<Format>,
+
+    #[structopt(long = "strict")]
+    /// Treat warnings as errors
+    strict: bool,
+
+    #[structopt(long = "system")]
+    /// The steno system the lessons are written in, when not checking against a database
+    system: Option<String>,
+
+    #[structopt(name = "FILE")]
+    files: Vec<String>,
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "sdrill", about = "Steno drilling util")]
 struct Opt {
@@ -160,9 +411,12 @@ fn main() -> Result<()> {
             info!("Starting learn mode");
             let tapefile = args.tape_file.as_ref().map(|n| open_tape_file(n)).transpose()?;
             let tapefile = tapefile.map(|f| Box::new(f) as Box<dyn Write>);
-            let db = Db::open(&args.file)?;
-            let app = LearnApp::new_learn(args.new, args.limit);
-            let mut ui = Ui::new(db, Box::new(app), tapefile)?;
+            let db = args.input.open_db(&args.file)?;
+            let dict = Dictionary::load(&args.dicts)?;
+            let app = LearnApp::new_learn(args.new, args.limit, args.prefer_canonical)
+                .with_dictionary(dict);
+            let source = args.input.source()?;
+            let mut ui = Ui::new(db, Box::new(app), source, tapefile)?;
             ui.run(args.learn_time)?;
         }
 
@@ -170,31 +424,80 @@ fn main() -> Result<()> {
             info!("Starting drill mode");
             let tapefile = args.tape_file.as_ref().map(|n| open_tape_file(n)).transpose()?;
             let tapefile = tapefile.map(|f| Box::new(f) as Box<dyn Write>);
-            let db = Db::open(&args.file)?;
+            let db = args.input.open_db(&args.file)?;
             let _ = args.repeat;
-            let app = LearnApp::new_drill(args.list);
-            let mut ui = Ui::new(db, Box::new(app), tapefile)?;
+            let dict = Dictionary::load(&args.dicts)?;
+            let app = LearnApp::new_drill(args.list).with_dictionary(dict);
+            let source = args.input.source()?;
+            let mut ui = Ui::new(db, Box::new(app), source, tapefile)?;
             ui.run(None)?;
         }
 
         Command::Import(args) => {
+            if args.name.is_some() && args.files.len() > 1 {
+                bail!("--name can only be given when importing a single file");
+            }
+
+            // Open the database first, so that the keys are read with its steno system.
             let mut db = Db::open(&args.file)?;
 
+            let filter = Filter {
+                keys: args.keys.as_deref().map(Stroke::from_text).transpose()?,
+                max_strokes: args.max_strokes,
+                words: args.words.as_ref().map(Filter::load_words).transpose()?,
+            };
+
+            let mode = if args.strict { Mode::Strict } else { Mode::Lenient };
+
+            if let Some(manifest) = &args.course {
+                if !args.files.is_empty() || args.name.is_some() {
+                    bail!("--course can't be combined with lesson files or --name");
+                }
+                let course = Course::load(manifest)?;
+                let lessons = course.load_lessons(args.format, &filter, mode)?;
+                db.load_course(&course, &lessons)?;
+            }
+
             for name in args.files {
                 println!("import: {}", name);
-                let lesson = Lesson::load(name)?;
+                let lesson = Lesson::load(name, args.format, args.name.as_deref(), &filter, mode)?;
+                for warning in &lesson.warnings {
+                    println!("warning: {}", warning);
+                }
                 // println!("lesson: {:#?}", lesson);
-                db.load(&lesson)?;
+                if args.update {
+                    db.update_lesson(&lesson)?;
+                } else {
+                    db.load(&lesson)?;
+                }
+            }
+
+            if args.update || args.course.is_some() {
+                let stale = db.get_stale()?;
+                if !stale.is_empty() {
+                    println!(
+                        "{} words being learned have changed outlines, run 'sync' to update them",
+                        stale.len()
+                    );
+                }
             }
         }
 
         Command::Init(args) => {
             println!("Initializing database at: {:?}", args.file);
-            Db::init(&args.file)?;
+            Db::init(&args.file, &args.system)?;
         }
 
         Command::Info(args) => {
             let mut db = Db::open(&args.file)?;
+            let accuracy = Accuracy::load(&mut db)?;
+            if args.tui {
+                let app = Box::new(StatsApp::new(accuracy));
+                let source = Box::new(StrokeReader::new());
+                let mut ui = Ui::new(db, app, source, None)?;
+                ui.view()?;
+                return Ok(());
+            }
             db.info(args.seen, args.unseen, args.hide_learned)?;
             let due = db.get_due_count()?;
             println!();
@@ -214,6 +517,73 @@ fn main() -> Result<()> {
             let mins = db.get_minutes_practiced()?;
             println!("{:.1} minutes practiced, {:.1} hours",
                 mins, mins / 60.0);
+            println!();
+            errors::show_accuracy(&accuracy, 5);
+        }
+
+        Command::Sync(args) => {
+            let mode = if args.reset {
+                SyncMode::Reset
+            } else if args.review {
+                SyncMode::Review
+            } else if args.apply {
+                SyncMode::Update
+            } else {
+                SyncMode::Show
+            };
+            let mut db = Db::open(&args.file)?;
+            let count = db.sync(mode)?;
+            if count == 0 {
+                println!("All words being learned match their lessons");
+            } else if mode == SyncMode::Show {
+                println!("{} words differ, use --apply, --review or --reset to update", count);
+            }
+        }
+
+        Command::Migrate(args) => {
+            if args.dry_run {
+                Db::migrate_dry_run(&args.file)?;
+            } else {
+                // Opening the database performs any needed upgrade.
+                Db::open(&args.file)?;
+            }
+        }
+
+        Command::Export(args) => {
+            let mut db = Db::open(&args.file)?;
+            let list = match args.list.parse::<usize>() {
+                Ok(id) => id,
+                Err(_) => match db.find_list(&args.list)? {
+                    Some(id) => id,
+                    None => bail!("There is no list named {:?}", args.list),
+                },
+            };
+            let lesson = db.get_lesson(list)?;
+            match &args.output {
+                Some(name) => lesson.write(&mut File::create(name)?, args.format)?,
+                None => lesson.write(&mut std::io::stdout().lock(), args.format)?,
+            }
+        }
+        Command::Errors(args) => {
+            let mut db = Db::open(&args.file)?;
+            errors::show(&mut db, args.days, args.top)?;
+        }
+
+        Command::Slowest(args) => {
+            let mut db = Db::open(&args.file)?;
+            hesitation::show(&mut db, args.days, args.top)?;
+        }
+
+        Command::Check(args) => {
+            if let Some(system) = &args.system {
+                system::set_current(System::find(system)?.0);
+            }
+            let mut db = args.file.as_ref().map(Db::open).transpose()?;
+            let report = check::check(&args.files, args.format, db.as_mut())?;
+            println!("{} errors, {} warnings", report.errors, report.warnings);
+            if !report.passed(args.strict) {
+                bail!("Lesson check failed");
+            }
         }
 
         Command::ToLearn(args) => {
@@ -266,11 +636,11 @@ fn nice_time(time: f64) -> String {
         }
         // The text consists of a number, followed by letters giving the unit.  We want the number
         // to be right justified.
-        let digits = piece.chars().take_while(|ch| ch.is_digit(10)).count();
+        let digits = piece.chars().take_while(|ch| ch.is_ascii_digit()).count();
         for _ in digits .. 3 {
             result.push(' ');
         }
-        result.push_str(&piece);
+        result.push_str(piece);
         for _ in piece.len() - digits .. 6 {
             result.push(' ');
         }
@@ -288,8 +658,64 @@ fn nice_time(time: f64) -> String {
     result
 }
 
+impl InputArgs {
+    // Strokes are read from Plover through the keyboard, unless they are written on the keyboard
+    // itself, come from a steno machine or a log, or a tape is being replayed.
+    fn source(&self) -> Result<Box<dyn StrokeSource>> {
+        let given = [&self.gemini, &self.txbolt, &self.log, &self.replay];
+        let count = given.iter().filter(|arg| arg.is_some()).count() + self.keyboard.iter().count();
+        if count > 1 {
+            bail!("Only one of --keyboard, --gemini, --txbolt, --log and --replay can be given");
+        }
+        if self.layout.is_some() && self.keyboard.is_none() {
+            bail!("--layout is only used with --keyboard");
+        }
+
+        Ok(if let Some(mode) = self.keyboard {
+            let layout = match &self.layout {
+                Some(name) => Layout::load(name)?,
+                None => Layout::qwerty()?,
+            };
+            Box::new(KeyboardSteno::new(mode, layout)?)
+        } else if self.gemini.is_some() || self.txbolt.is_some() {
+            self.machine()?
+        } else if let Some(name) = &self.log {
+            Box::new(LogFollower::open(name)?)
+        } else if let Some(name) = &self.replay {
+            Box::new(TapeReplay::load(name, !self.fast)?)
+        } else {
+            Box::new(StrokeReader::new())
+        })
+    }
+
+    // Open the steno machine given by --gemini or --txbolt.
+    #[cfg(unix)]
+    fn machine(&self) -> Result<Box<dyn StrokeSource>> {
+        Ok(match (&self.gemini, &self.txbolt) {
+            (Some(name), _) => Box::new(GeminiReader::open(name, &self.exit_key)?),
+            (_, Some(name)) => Box::new(TxBoltReader::open(name)?),
+            (None, None) => unreachable!(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn machine(&self) -> Result<Box<dyn StrokeSource>> {
+        bail!("Steno machines (--gemini and --txbolt) can only be read on Unix systems")
+    }
+
+    // A replayed session is run against a copy of the database, so that the learner's progress
+    // isn't changed by practice they have already done.
+    fn open_db(&self, file: &str) -> Result<Db> {
+        if self.replay.is_some() {
+            Db::open_scratch(file)
+        } else {
+            Db::open(file)
+        }
+    }
+}
+
 fn open_tape_file(name: &str) -> Result<File> {
-    let mut fd = File::options().write(true).append(true).create(true).open(name)?;
+    let mut fd = File::options().append(true).create(true).open(name)?;
     let now = Local::now();
     writeln!(fd, "{}", now)?;
     Ok(fd)
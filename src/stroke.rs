@@ -8,19 +8,26 @@
 //! section is not, there will be a single '-' before the right characters (otherwise, the stroke
 //! might be ambiguous).
 //!
-//! Our parser is currently fairly strict and requires the hyphen to be present.
+//! The parser requires the hyphen to be present when it is needed, but otherwise accepts the
+//! various equivalent spellings that Plover and other engines produce, such as a '-' after the
+//! star ("*-T"), or a '#' along with digits ("#1-9").
 //!
 //! The number bar can be textually represented by the '#' if needed to disambiguate.  If there are
 //! any number row characters present, the '#' is not needed.
+//!
+//! The layout of the keys comes from the current [`system::System`], which is the English layout
+//! described here unless the database selects a different one.
 
 // Until everything is used.
 #![allow(dead_code)]
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, Result};
 use std::fmt;
 
+pub mod system;
+
 /// The stroke itself is just a 32 bit number.  It represents a single stroke on the machine.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Stroke(u32);
 
 /// A steno word is a group of strokes that are represented separated by slashes.
@@ -31,89 +38,62 @@ pub struct StenoWord(pub Vec<Stroke>);
 #[derive(Clone, Debug)]
 pub struct StenoPhrase(pub Vec<StenoWord>);
 
-static NORMAL: &str = "^+STKPWHRAO*EUFRPBLGTSDZ";
-static NUMS: &str = "^+12K3W4R50*EU6R7B8G9SDZ";
+/// How strictly to parse strokes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    /// Questionable strokes are accepted, and returned with warnings.
+    Lenient,
+    /// Questionable strokes are errors.
+    Strict,
+}
 
-// #ST KPWH RAO* EURF PBLG TSDZ
+/// Something wrong with the text of a stroke.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Problem {
+    /// A character that isn't a key, or is out of steno order.
+    InvalidChar(char),
+    /// A '-' after keys that come after the hyphen.
+    MisplacedHyphen,
+    /// A key written as its letter along with the number bar, as in "#S-Z", which would be
+    /// written with its digit, "1-Z".  This is only a warning, as the stroke is still understood,
+    /// but is likely a mistake.  Plover reads these spellings, which is why they are accepted, but
+    /// always writes the digits itself, in both its dictionaries and its stroke log.  So in a
+    /// lesson the letter is more likely to be a typo than a deliberate spelling, and the warning
+    /// is kept even though the stroke is valid.
+    LetterWithNumber(char, char),
+}
 
-// Various masks.
-// static LEFT: Stroke = Stroke(0x7f8000);
-static MID: Stroke = Stroke(0x007c00);
-static RIGHT: Stroke = Stroke(0x0003ff);
-static NUM: Stroke = Stroke(0x1000000);
-static DIGITS: Stroke = Stroke(0x3562a8);
-static STAR: Stroke = Stroke(0x001000);
-static CARET: Stroke = Stroke(0x800000);
-static PLUS: Stroke = Stroke(0x400000);
+/// A problem found when parsing, along with where it was found.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// The text being parsed.
+    pub text: String,
+    /// The position of the problem in `text`, in characters.
+    pub pos: usize,
+    pub problem: Problem,
+}
+
+/// A successfully parsed stroke, along with any warnings about it.
+#[derive(Clone, Debug)]
+pub struct Parsed {
+    pub stroke: Stroke,
+    pub warnings: Vec<Diagnostic>,
+}
 
 impl Stroke {
+    /// Parse a stroke, accepting questionable strokes without comment.  Use `parse` to find out
+    /// about those.
     pub fn from_text(text: &str) -> Result<Stroke> {
-        let mut result = 0u32;
-        let mut bit = NUM.0;
-        let mut must_not_num = false;
-
-        let mut norms = NORMAL.chars();
-        let mut nums = NUMS.chars();
-
-        for ch in text.chars() {
-            if ch == '#' {
-                result |= NUM.0;
-                continue;
-            }
-
-            if ch == '-' {
-                if bit < MID.0 {
-                    bail!("Invalid placement of '-' in stroke: {:?}", text);
-                }
-
-                while bit > MID.0 {
-                    bit >>= 1;
-                    if norms.next().is_some() {
-                    } else {
-                        panic!("State error");
-                    }
-                    if nums.next().is_none() {
-                        panic!("State error");
-                    }
-                }
-
-                continue;
-            }
-
-            loop {
-                // Get the next normal a numeric character, and the next bit to go with that.
-                bit >>= 1;
-                let norm = if let Some(n) = norms.next() {
-                    n
-                } else {
-                    bail!("Invalid character: {} in stroke", ch);
-                };
-                let num = if let Some(n) = nums.next() {
-                    n
-                } else {
-                    panic!("Unexpected state");
-                };
-
-                if ch == norm {
-                    result |= bit;
-                    if ch != num {
-                        must_not_num = true;
-                    }
-                    break;
-                } else if ch == num {
-                    result |= bit | NUM.0;
-                    break;
-                }
-
-                // The character didn't match, go on to the next one.
-            }
-        }
+        Ok(Stroke::parse(text, Mode::Lenient)?.stroke)
+    }
 
-        if (result & NUM.0) != 0 && must_not_num {
-            println!("Warning stroke: {:?}", text);
-            // bail!("Stroke has # and inappropriate character");
+    /// Parse a stroke.  In strict mode, the first warning is returned as the error.
+    pub fn parse(text: &str, mode: Mode) -> std::result::Result<Parsed, Diagnostic> {
+        let mut parsed = system::current().parse_stroke(text)?;
+        if mode == Mode::Strict && !parsed.warnings.is_empty() {
+            return Err(parsed.warnings.remove(0));
         }
-        Ok(Stroke(result))
+        Ok(parsed)
     }
 
     /// Determine if this stroke has any of the keys pressed in 'other'.
@@ -121,138 +101,101 @@ impl Stroke {
         (self.0 & other.0) != 0
     }
 
+    /// Determine if every key pressed in this stroke is also pressed in 'other'.
+    pub fn is_subset(self, other: Stroke) -> bool {
+        (self.0 & !other.0) == 0
+    }
+
+    /// The keys pressed in either stroke.
+    pub fn union(self, other: Stroke) -> Stroke {
+        Stroke(self.0 | other.0)
+    }
+
+    /// The keys pressed in this stroke, but not in 'other'.
+    pub fn without(self, other: Stroke) -> Stroke {
+        Stroke(self.0 & !other.0)
+    }
+
+    /// Each of the keys pressed in this stroke, as a stroke of its own.  They are in steno order,
+    /// with the number bar first.
+    pub fn keys(self) -> impl Iterator<Item = Stroke> {
+        (0..32)
+            .rev()
+            .map(|bit| Stroke(1 << bit))
+            .filter(move |&key| self.has_any(key))
+    }
+
     /// Return the paper tape representation of the stroke.
     #[allow(dead_code)]
     pub fn to_tape(self) -> String {
-        let mut buf = String::with_capacity(NORMAL.len() + 1);
-
-        let chars = if self.has_any(NUM) { NUMS } else { NORMAL };
-        let mut bit = NUM.0;
-        if self.has_any(Stroke(bit)) {
-            buf.push('#');
-        } else {
-            buf.push(' ');
-        }
-        for ch in chars.chars() {
-            bit >>= 1;
-            if self.has_any(Stroke(bit)) {
-                buf.push(ch);
-            } else {
-                buf.push(' ');
-            }
-        }
-
-        buf.to_string()
+        system::current().tape(self)
     }
 
-    /// Is this stroke the star?  We also allow the caret or plus keys, alone, to delete, presuming
-    /// this will also be in the user's dictionary.
-    pub fn is_star(self) -> bool {
-        self == STAR ||
-            self == CARET ||
-            self == PLUS
+    /// Read a stroke from its paper tape representation.
+    pub fn from_tape(text: &str) -> Option<Stroke> {
+        system::current().read_tape(text)
     }
-}
 
-// A builder that can generate stroke diagrams.
-// ___________#_____________
-// │S│T│P│H│ │*│ │F│P│L│T│D│
-// │S│K│W│R│ │*│ │R│B│G│S│Z│
-// ╰─┴─┴─┴─╯ ╰─╯ ╰─┴─┴─┴─┴─╯
-//       │A│O│ │E│U│
-//       ╰─┴─╯ ╰─┴─╯
-pub struct Diagrammer {
-    template: Vec<Vec<Place>>,
-}
-
-enum Place {
-    Stroke(char, Stroke),
-    Text(char),
-}
+    /// The width of the paper tape representation of strokes.
+    pub fn tape_width() -> usize {
+        system::current().tape(Stroke(0)).chars().count()
+    }
 
-impl Diagrammer {
-    pub fn new() -> Diagrammer {
-        static ROWS: &[&str] = &[
-            "___________#_____________",
-            "│S│T│P│H│ │*│ │F│P│L│T│D│",
-            "│S│K│W│R│ │*│ │R│B│G│S│Z│",
-            "╰─┴─┴─┴─╯ ╰─╯ ╰─┴─┴─┴─┴─╯",
-            "      │A│O│ │E│U│",
-            "      ╰─┴─╯ ╰─┴─╯",
-        ];
-
-        let template = ROWS
-            .iter()
-            .map(|row| {
-                row.chars()
-                    .enumerate()
-                    .map(|(col, ch)| {
-                        let line = if col > 9 {
-                            format!("-{}", ch)
-                        } else {
-                            format!("{}", ch)
-                        };
-                        match Stroke::from_text(&line) {
-                            Ok(st) => Place::Stroke(ch, st),
-                            Err(_) => Place::Text(ch),
-                        }
-                    })
-                    .collect()
-            })
-            .collect();
-
-        Diagrammer { template }
+    /// Is this stroke the star?  In the default system, we also allow the caret or plus keys,
+    /// alone, to delete, presuming this will also be in the user's dictionary.
+    pub fn is_star(self) -> bool {
+        system::current().is_undo(self)
     }
 
-    pub fn to_diagram(&self, stroke: Stroke) -> Vec<String> {
-        self.template
-            .iter()
-            .map(|row| {
-                let mut line = String::new();
-                for cell in row.iter() {
-                    match cell {
-                        Place::Text(t) => line.push(*t),
-                        Place::Stroke(ch, st) => {
-                            if stroke.has_any(*st) {
-                                line.push_str("\x1b[7m");
-                            } else {
-                                line.push_str("\x1b[37m");
-                            }
-                            line.push(*ch);
-                            line.push_str("\x1b[0m");
-                        }
-                    }
-                }
-                line
-            })
-            .collect()
+    /// The stroke that undoes the previous one.
+    pub fn undo() -> Result<Stroke> {
+        system::current()
+            .undo()
+            .ok_or_else(|| anyhow!("The steno system has no undo stroke"))
     }
 }
 
 // Display is in canoncal order.
 impl fmt::Display for Stroke {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // The '#' should be printed if the number is present, but none of the digits are present.
-        if self.has_any(NUM) && !self.has_any(DIGITS) {
-            write!(f, "#")?;
+        system::current().write_stroke(f, *self)
+    }
+}
+
+impl Diagnostic {
+    /// Make this diagnostic refer to the larger `text`, that the text parsed starts at `start`
+    /// within.
+    pub fn within(self, text: &str, start: usize) -> Diagnostic {
+        Diagnostic {
+            text: text.to_string(),
+            pos: self.pos + start,
+            problem: self.problem,
         }
-        let need_hyphen = self.has_any(RIGHT) && !self.has_any(MID);
-        let chars = if self.has_any(NUM) { NUMS } else { NORMAL };
-        let mut bit = NUM.0 >> 1;
-        for ch in chars.chars() {
-            if ch == '*' && need_hyphen {
-                write!(f, "-")?;
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::InvalidChar(ch) => {
+                write!(f, "{:?} is not a key, or is out of steno order", ch)
             }
-            if self.has_any(Stroke(bit)) {
-                write!(f, "{}", ch)?;
+            Problem::MisplacedHyphen => write!(f, "'-' comes after keys on the right"),
+            Problem::LetterWithNumber(ch, digit) => {
+                write!(f, "{:?} is used with '#', which makes it {:?}", ch, digit)
             }
-            bit >>= 1;
         }
+    }
+}
 
-        Ok(())
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}, column {}: {}", self.text, self.pos + 1, self.problem)
     }
 }
 
+impl std::error::Error for Diagnostic {}
+
 #[test]
 fn stroke_roundtrip() {
     for ch in 1u32..0x2000000 {
@@ -265,12 +208,143 @@ fn stroke_roundtrip() {
     }
 }
 
+#[test]
+fn stroke_diagnostics() {
+    let err = Stroke::parse("STKQ", Mode::Lenient).unwrap_err();
+    assert_eq!((err.pos, err.problem), (3, Problem::InvalidChar('Q')));
+    let err = Stroke::parse("-TS-", Mode::Lenient).unwrap_err();
+    assert_eq!((err.pos, err.problem), (3, Problem::MisplacedHyphen));
+
+    let parsed = Stroke::parse("#S-Z", Mode::Lenient).unwrap();
+    assert_eq!(parsed.stroke.to_string(), "1-Z");
+    assert_eq!(parsed.warnings.len(), 1);
+    assert_eq!(parsed.warnings[0].problem, Problem::LetterWithNumber('S', '1'));
+    let err = Stroke::parse("#S-Z", Mode::Strict).unwrap_err();
+    assert_eq!(err.pos, 1);
+    assert!(Stroke::parse("#-Z", Mode::Strict).is_ok());
+
+    // Positions are within the whole phrase.
+    let mut warnings = vec![];
+    let err = StenoPhrase::parse_checked("KAT/-S TKOQ", Mode::Lenient, &mut warnings).unwrap_err();
+    assert_eq!((err.text.as_str(), err.pos), ("KAT/-S TKOQ", 10));
+}
+
+#[test]
+fn plover_spellings() {
+    // Each of the ways that a stroke is written by Plover or other engines, and how it is shown.
+    // Spellings with '#' and letters, such as "#S-T", are read, but still warn, as described for
+    // Problem::LetterWithNumber.
+    let table = [
+        ("1-9", "1-9"),
+        ("19", "1-9"),
+        ("#1-9", "1-9"),
+        ("#19", "1-9"),
+        ("#S-T", "1-9"),
+        ("1-T", "1-9"),
+        ("-9", "-9"),
+        ("#-T", "-9"),
+        ("-6", "-6"),
+        ("1234", "1234"),
+        ("#STPH", "1234"),
+        ("#STKPWH", "12K3W4"),
+        ("0", "0"),
+        ("#0", "0"),
+        ("#O", "0"),
+        ("O-", "O"),
+        ("50", "50"),
+        ("509", "509"),
+        ("50-9", "509"),
+        ("#AO-T", "509"),
+        ("*T", "*T"),
+        ("*-T", "*T"),
+        ("AO-T", "AOT"),
+        ("EU-F", "EUF"),
+        ("E-T", "ET"),
+        ("-T", "-T"),
+        ("T-", "T"),
+        ("#", "#"),
+        ("#-Z", "#-Z"),
+    ];
+    for (text, shown) in table {
+        let stroke = Stroke::parse(text, Mode::Lenient).unwrap().stroke;
+        assert_eq!(stroke.to_string(), shown, "reading {:?}", text);
+        assert_eq!(Stroke::from_text(shown).unwrap(), stroke, "reading {:?}", shown);
+    }
+
+    // A '-' can't come after keys on the right.
+    assert!(Stroke::parse("ET-", Mode::Lenient).is_err());
+    assert!(Stroke::parse("1-9-", Mode::Lenient).is_err());
+}
+
 impl StenoPhrase {
     pub fn parse(text: &str) -> Result<StenoPhrase> {
-        let words: Result<Vec<_>> = text.split(' ').map(|w| StenoWord::parse(w)).collect();
+        let words: Result<Vec<_>> = text.split(' ').map(StenoWord::parse).collect();
         Ok(StenoPhrase(words?))
     }
 
+    /// Parse a phrase, adding any warnings about the strokes to `warnings`.  The diagnostics refer
+    /// to positions in the whole phrase.
+    pub fn parse_checked(
+        text: &str,
+        mode: Mode,
+        warnings: &mut Vec<Diagnostic>,
+    ) -> std::result::Result<StenoPhrase, Diagnostic> {
+        let mut words = vec![];
+        let mut pos = 0;
+        for word in text.split(' ') {
+            let mut strokes = vec![];
+            for st in word.split('/') {
+                let parsed = Stroke::parse(st, mode).map_err(|e| e.within(text, pos))?;
+                warnings.extend(parsed.warnings.into_iter().map(|w| w.within(text, pos)));
+                strokes.push(parsed.stroke);
+                pos += st.chars().count() + 1;
+            }
+            words.push(StenoWord(strokes));
+        }
+        Ok(StenoPhrase(words))
+    }
+
+    /// Parse a list of phrases separated by '|'.  This is used where a word has alternate
+    /// outlines.  An empty string gives an empty list.
+    pub fn parse_list(text: &str) -> Result<Vec<StenoPhrase>> {
+        text.split('|')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(StenoPhrase::parse)
+            .collect()
+    }
+
+    /// Parse a list of phrases, collecting warnings as `parse_checked` does.  The diagnostics
+    /// refer to positions in the whole list.
+    pub fn parse_list_checked(
+        text: &str,
+        mode: Mode,
+        warnings: &mut Vec<Diagnostic>,
+    ) -> std::result::Result<Vec<StenoPhrase>, Diagnostic> {
+        let mut result = vec![];
+        let mut pos = 0;
+        for item in text.split('|') {
+            let trimmed = item.trim_start();
+            let start = pos + item.chars().count() - trimmed.chars().count();
+            let trimmed = trimmed.trim_end();
+            if !trimmed.is_empty() {
+                let mut phrase_warnings = vec![];
+                let phrase = StenoPhrase::parse_checked(trimmed, mode, &mut phrase_warnings)
+                    .map_err(|e| e.within(text, start))?;
+                warnings.extend(phrase_warnings.into_iter().map(|w| w.within(text, start)));
+                result.push(phrase);
+            }
+            pos += item.chars().count() + 1;
+        }
+        Ok(result)
+    }
+
+    /// Format a list of phrases, the inverse of `parse_list`.
+    pub fn join_list(list: &[StenoPhrase]) -> String {
+        let list: Vec<_> = list.iter().map(|p| p.to_string()).collect();
+        list.join(" | ")
+    }
+
     pub fn linear(&self) -> Vec<Stroke> {
         let mut result = vec![];
         for w in &self.0 {
@@ -284,7 +358,7 @@ impl StenoPhrase {
 
 impl StenoWord {
     pub fn parse(text: &str) -> Result<StenoWord> {
-        let strokes: Result<Vec<_>> = text.split('/').map(|w| Stroke::from_text(w)).collect();
+        let strokes: Result<Vec<_>> = text.split('/').map(Stroke::from_text).collect();
         Ok(StenoWord(strokes?))
     }
 }
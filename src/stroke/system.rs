@@ -0,0 +1,449 @@
+// SPDX-License-Identifier: GPL-3.0
+//! Steno system definitions.
+//!
+//! A system describes the keys of a steno layout: their order, which side of the hyphen they are
+//! on, what they become with the number bar, and which strokes undo the previous one.  Systems are
+//! written in TOML, in much the same terms as a Plover system plugin.  The English layout is
+//! built in, and is used unless a database selects another.
+//!
+//! A stroke is a bit for each key, with the first key in the highest bit, and the number bar
+//! above all of them.
+//!
+//! Strokes are read in any of the equivalent ways that Plover and other engines write them, so
+//! that, for example, "1-9", "19", "#S-T" and "#1-9" are all the same stroke, as are "*T" and
+//! "*-T".  They are always shown in the canonical form.
+
+use super::{Diagnostic, Parsed, Problem, Stroke};
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// The definition of the built-in English system.
+pub static ENGLISH: &str = include_str!("../../systems/english.toml");
+
+/// A system, as written in TOML.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Definition {
+    name: String,
+    keys: Vec<String>,
+    #[serde(default)]
+    implicit_hyphen: Vec<String>,
+    #[serde(default)]
+    numbers: BTreeMap<String, String>,
+    #[serde(default)]
+    undo: Vec<String>,
+    #[serde(default)]
+    diagram: Vec<String>,
+}
+
+/// A steno system, ready to be used to read and show strokes.
+#[derive(Debug, PartialEq)]
+pub struct System {
+    pub name: String,
+    // The name of each key, such as "S-" or "-T".
+    names: Vec<String>,
+    // The letter of each key, in steno order, and the letter when used with the number bar.
+    keys: Vec<char>,
+    nums: Vec<char>,
+    // The position that a '-' is written at, and the position of the first key after it that
+    // isn't an implicit hyphen key.  A '-' can be read anywhere between the two.
+    hyphen: usize,
+    first_right: usize,
+    // The number bar, or 0 if there isn't one.
+    num: u32,
+    // The implicit hyphen keys.
+    implicit: u32,
+    // The keys on the right that aren't implicit hyphen keys.
+    right: u32,
+    // The keys that are different with the number bar.
+    digits: u32,
+    undo: Vec<Stroke>,
+    diagram: Vec<String>,
+}
+
+static CURRENT: RwLock<Option<Arc<System>>> = RwLock::new(None);
+
+/// The system strokes are read and shown with.
+pub fn current() -> Arc<System> {
+    match &*CURRENT.read().unwrap() {
+        Some(system) => system.clone(),
+        None => System::english(),
+    }
+}
+
+/// Change the system that strokes are read and shown with.  This is done when a database is
+/// opened, before any strokes have been read.  Nothing changes if it is the same system.
+pub fn set_current(system: System) {
+    if *current() != system {
+        *CURRENT.write().unwrap() = Some(Arc::new(system));
+    }
+}
+
+impl System {
+    /// The built-in English system.
+    pub fn english() -> Arc<System> {
+        static SYSTEM: OnceLock<Arc<System>> = OnceLock::new();
+        SYSTEM
+            .get_or_init(|| Arc::new(System::parse(ENGLISH).expect("Built-in system is valid")))
+            .clone()
+    }
+
+    /// Look up a built-in system by name.
+    pub fn builtin(name: &str) -> Option<&'static str> {
+        match name {
+            "english" => Some(ENGLISH),
+            _ => None,
+        }
+    }
+
+    /// Find a system given either the name of a built-in system or a file defining one.  Also
+    /// returns the definition, for systems that aren't built in.
+    pub fn find(spec: &str) -> Result<(System, Option<String>)> {
+        if let Some(text) = System::builtin(spec) {
+            return Ok((System::parse(text)?, None));
+        }
+        let text = fs::read_to_string(spec)
+            .map_err(|e| anyhow!("Unknown steno system {:?}: {}", spec, e))?;
+        let system = System::parse(&text).map_err(|e| anyhow!("{}: {}", spec, e))?;
+        Ok((system, Some(text)))
+    }
+
+    /// Parse a system definition, checking that it is consistent.
+    pub fn parse(text: &str) -> Result<System> {
+        let def: Definition = toml::from_str(text)?;
+
+        let count = def.keys.len();
+        if count == 0 || count > 31 {
+            bail!("System {:?} must have between 1 and 31 keys", def.name);
+        }
+
+        let mut keys = vec![];
+        let mut seen_right = false;
+        let mut right_start = count;
+        for (pos, key) in def.keys.iter().enumerate() {
+            let (ch, side) = key_letter(key)?;
+            match side {
+                Side::Left if seen_right => {
+                    bail!("Key {:?} is on the left, but comes after keys on the right", key)
+                }
+                Side::Right if !seen_right => {
+                    seen_right = true;
+                    right_start = pos;
+                }
+                _ => (),
+            }
+            if def.keys[..pos].contains(key) {
+                bail!("Key {:?} is given more than once", key);
+            }
+            keys.push(ch);
+        }
+
+        let find = |key: &str| {
+            def.keys
+                .iter()
+                .position(|k| k == key)
+                .ok_or_else(|| anyhow!("{:?} is not one of the keys", key))
+        };
+        let bit = |pos: usize| 1u32 << (count - 1 - pos);
+
+        let mut nums = keys.clone();
+        let mut digits = 0;
+        for (key, num) in &def.numbers {
+            let pos = find(key)?;
+            nums[pos] = key_letter(num)?.0;
+            digits |= bit(pos);
+        }
+
+        let mut implicit = 0;
+        let mut hyphen = right_start;
+        for key in &def.implicit_hyphen {
+            let pos = find(key)?;
+            implicit |= bit(pos);
+            hyphen = hyphen.min(pos);
+        }
+
+        let right = (right_start..count).fold(0, |mask, pos| mask | bit(pos)) & !implicit;
+
+        let mut system = System {
+            name: def.name,
+            names: def.keys,
+            keys,
+            nums,
+            hyphen,
+            first_right: (0..count).find(|&pos| right & bit(pos) != 0).unwrap_or(count),
+            num: if def.numbers.is_empty() { 0 } else { 1 << count },
+            implicit,
+            right,
+            digits,
+            undo: vec![],
+            diagram: def.diagram,
+        };
+        for text in &def.undo {
+            let stroke = system.parse_stroke(text)?.stroke;
+            system.undo.push(stroke);
+        }
+        Ok(system)
+    }
+
+    /// Parse a stroke.  Keys written as letters along with the number bar, where they would be
+    /// digits, are warned about.
+    pub fn parse_stroke(&self, text: &str) -> std::result::Result<Parsed, Diagnostic> {
+        let count = self.keys.len();
+        let mut result = 0u32;
+        let mut pos = 0;
+        // The first key written as a letter that has a digit, and where it was.
+        let mut letter = None;
+
+        let error = |col: usize, problem: Problem| Diagnostic {
+            text: text.to_string(),
+            pos: col,
+            problem,
+        };
+
+        for (col, ch) in text.chars().enumerate() {
+            if ch == '#' && self.num != 0 {
+                result |= self.num;
+                continue;
+            }
+
+            if ch == '-' {
+                // Some engines write a '-' after the implicit hyphen keys, as in "*-T" or "50-9",
+                // which is the same as leaving it out.
+                if pos > self.first_right {
+                    return Err(error(col, Problem::MisplacedHyphen));
+                }
+                pos = pos.max(self.hyphen);
+                continue;
+            }
+
+            loop {
+                // Look for the character among the remaining keys, as either the key itself, or
+                // the key with the number bar.
+                if pos >= count {
+                    return Err(error(col, Problem::InvalidChar(ch)));
+                }
+                let bit = 1 << (count - 1 - pos);
+                let (norm, num) = (self.keys[pos], self.nums[pos]);
+                pos += 1;
+
+                if ch == norm {
+                    result |= bit;
+                    if ch != num && letter.is_none() {
+                        letter = Some((col, ch, num));
+                    }
+                    break;
+                } else if ch == num {
+                    result |= bit | self.num;
+                    break;
+                }
+            }
+        }
+
+        let mut warnings = vec![];
+        if let Some((col, ch, digit)) = letter {
+            if result & self.num != 0 {
+                warnings.push(error(col, Problem::LetterWithNumber(ch, digit)));
+            }
+        }
+        Ok(Parsed {
+            stroke: Stroke(result),
+            warnings,
+        })
+    }
+
+    /// Write the stroke in canonical order.
+    pub fn write_stroke(&self, f: &mut fmt::Formatter, stroke: Stroke) -> fmt::Result {
+        // The '#' should be printed if the number is present, but none of the digits are present.
+        let number = stroke.has_any(Stroke(self.num));
+        if number && !stroke.has_any(Stroke(self.digits)) {
+            write!(f, "#")?;
+        }
+        let need_hyphen =
+            stroke.has_any(Stroke(self.right)) && !stroke.has_any(Stroke(self.implicit));
+        let chars = if number { &self.nums } else { &self.keys };
+        for (pos, ch) in chars.iter().enumerate() {
+            if pos == self.hyphen && need_hyphen {
+                write!(f, "-")?;
+            }
+            if stroke.has_any(Stroke(1 << (chars.len() - 1 - pos))) {
+                write!(f, "{}", ch)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return the paper tape representation of the stroke.
+    pub fn tape(&self, stroke: Stroke) -> String {
+        let mut buf = String::with_capacity(self.keys.len() + 1);
+
+        let number = stroke.has_any(Stroke(self.num));
+        buf.push(if number { '#' } else { ' ' });
+        let chars = if number { &self.nums } else { &self.keys };
+        for (pos, &ch) in chars.iter().enumerate() {
+            if stroke.has_any(Stroke(1 << (chars.len() - 1 - pos))) {
+                buf.push(ch);
+            } else {
+                buf.push(' ');
+            }
+        }
+
+        buf
+    }
+
+    /// Read the paper tape representation of a stroke, as given by `tape`.  Returns None if the
+    /// text isn't a stroke on the tape.
+    pub fn read_tape(&self, text: &str) -> Option<Stroke> {
+        let count = self.keys.len();
+        let mut chars = text.chars();
+        let mut result = match chars.next()? {
+            '#' if self.num != 0 => self.num,
+            ' ' => 0,
+            _ => return None,
+        };
+        for (pos, ch) in chars.enumerate() {
+            if pos >= count {
+                return None;
+            }
+            if ch == self.keys[pos] || ch == self.nums[pos] {
+                result |= 1 << (count - 1 - pos);
+            } else if ch != ' ' {
+                return None;
+            }
+        }
+        Some(Stroke(result))
+    }
+
+    /// The key with the given name, such as "S-" or "-T", as Plover names them.  The number bar is
+    /// "#".
+    pub fn key_named(&self, name: &str) -> Option<Stroke> {
+        if name == "#" && self.num != 0 {
+            return Some(Stroke(self.num));
+        }
+        let pos = self.names.iter().position(|key| key == name)?;
+        Some(Stroke(1 << (self.keys.len() - 1 - pos)))
+    }
+
+    /// Is this stroke one that undoes the previous stroke?
+    pub fn is_undo(&self, stroke: Stroke) -> bool {
+        self.undo.contains(&stroke)
+    }
+
+    /// The stroke normally used to undo the previous stroke.
+    pub fn undo(&self) -> Option<Stroke> {
+        self.undo.first().copied()
+    }
+
+    /// Lay out the rows of a diagram of the keyboard.  Each position is either plain text or one
+    /// of the keys.  Letters are matched to keys in steno order along each row, so letters that
+    /// are on both sides are found on the correct side.  A system without a diagram is shown as a
+    /// single row of its keys.
+    pub fn diagram(&self) -> Vec<Vec<(char, Option<Stroke>)>> {
+        let rows = if self.diagram.is_empty() {
+            let mut row = String::new();
+            if self.num != 0 {
+                row.push('#');
+            }
+            row.extend(&self.keys);
+            vec![row]
+        } else {
+            self.diagram.clone()
+        };
+
+        let count = self.keys.len();
+        rows.iter()
+            .map(|row| {
+                let mut pos = 0;
+                row.chars()
+                    .map(|ch| {
+                        if ch == '#' && self.num != 0 {
+                            return (ch, Some(Stroke(self.num)));
+                        }
+                        match self.keys[pos.min(count)..].iter().position(|&k| k == ch) {
+                            Some(offset) => {
+                                pos += offset + 1;
+                                (ch, Some(Stroke(1 << (count - pos))))
+                            }
+                            None => (ch, None),
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+enum Side {
+    Left,
+    Middle,
+    Right,
+}
+
+// Split a key name, such as "S-" or "-Z", into its letter and the side of the keyboard it is on.
+fn key_letter(key: &str) -> Result<(char, Side)> {
+    let (text, side) = if let Some(text) = key.strip_suffix('-') {
+        (text, Side::Left)
+    } else if let Some(text) = key.strip_prefix('-') {
+        (text, Side::Right)
+    } else {
+        (key, Side::Middle)
+    };
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) if ch != '-' && ch != '#' => Ok((ch, side)),
+        _ => bail!("Invalid key name: {:?}", key),
+    }
+}
+
+#[test]
+fn other_system() {
+    // A small made up system, with no numbers or implicit hyphen keys.
+    let system = System::parse(
+        r#"
+        name = "tiny"
+        keys = ["S-", "T-", "-T", "-S"]
+        undo = ["ST"]
+        "#,
+    )
+    .unwrap();
+
+    let show = |stroke: Stroke| {
+        struct Show<'a>(&'a System, Stroke);
+        impl fmt::Display for Show<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.write_stroke(f, self.1)
+            }
+        }
+        Show(&system, stroke).to_string()
+    };
+
+    for bits in 1u32..16 {
+        let text = show(Stroke(bits));
+        assert_eq!(system.parse_stroke(&text).unwrap().stroke, Stroke(bits));
+    }
+    assert_eq!(show(Stroke(0b0001)), "-S");
+    assert_eq!(show(Stroke(0b1001)), "S-S");
+    assert_eq!(system.tape(Stroke(0b0110)), "  TT ");
+    assert_eq!(system.read_tape("  TT "), Some(Stroke(0b0110)));
+    assert_eq!(system.read_tape(" S"), Some(Stroke(0b1000)));
+    assert_eq!(system.read_tape(" S  S "), None);
+    assert_eq!(system.read_tape("2026-10-16"), None);
+    assert!(system.is_undo(Stroke(0b1100)));
+    assert!(system.parse_stroke("#S").is_err());
+    assert!(system.parse_stroke("T-S-").is_err());
+
+    assert!(System::parse("name = \"bad\"\nkeys = [\"-S\", \"T-\"]").is_err());
+    assert!(System::parse("name = \"bad\"\nkeys = [\"S-\", \"S-\"]").is_err());
+}
+
+#[test]
+fn same_system_kept() {
+    // Databases set their system each time they are opened, which is usually the one in use.
+    let before = current();
+    set_current(System::parse(ENGLISH).unwrap());
+    assert!(Arc::ptr_eq(&before, &current()));
+}
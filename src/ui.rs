@@ -1,8 +1,8 @@
 // SPDX-License-Identifier: GPL-3.0
 //! The textual ui.
 
-use crate::db::Db;
-use crate::input::{StrokeReader, Value};
+use crate::db::{get_now, Db};
+use crate::input::{StrokeSource, Value};
 use crate::stroke::{Stroke};
 use anyhow::Result;
 use crossterm::{
@@ -21,26 +21,35 @@ use tui::{
 };
 
 pub use learn::LearnApp;
+pub use stats::StatsApp;
 
+mod diagram;
 mod learn;
+mod stats;
 
 type UiBackend = CrosstermBackend<std::io::Stdout>;
 
 pub struct Ui {
-    terminal: Terminal<UiBackend>,
+    // The terminal, or None when running without one.
+    terminal: Option<Terminal<UiBackend>>,
     app: Box<dyn App>,
-    reader: StrokeReader,
+    reader: Box<dyn StrokeSource>,
     db: Db,
 
     // A possible place to record strokes.
     tapefile: Option<Box<dyn Write>>,
+    // When the session started, for the times on the tape.
+    started: f64,
 }
 
 /// The application is controlled via this trait.
 pub trait App {
     fn update_status(&mut self, db: &mut Db) -> Result<()>;
     fn update(&mut self, db: &mut Db) -> Result<bool>;
-    fn add_stroke(&mut self, stroke: Stroke, db: &mut Db) -> Result<bool>;
+    /// Add a stroke, written at `time`.  Returns true when there is nothing more to do.
+    fn add_stroke(&mut self, stroke: Stroke, time: f64, db: &mut Db) -> Result<bool>;
+    /// Tell the user about input that couldn't be used.
+    fn show_problem(&mut self, text: String);
 
     fn set_learntime(&mut self, learn_time: Option<usize>);
     fn goodbye_ref(&self) -> Option<&str>;
@@ -49,21 +58,35 @@ pub trait App {
 }
 
 impl Ui {
-    pub fn new(db: Db, app: Box<dyn App>, tapefile: Option<Box<dyn Write>>) -> Result<Ui> {
+    pub fn new(
+        db: Db,
+        app: Box<dyn App>,
+        reader: Box<dyn StrokeSource>,
+        tapefile: Option<Box<dyn Write>>,
+    ) -> Result<Ui> {
         let mut stdout = io::stdout();
         enable_raw_mode()?;
         execute!(stdout, EnterAlternateScreen)?;
         let backend = CrosstermBackend::new(stdout);
         let terminal = Terminal::new(backend)?;
-        let reader = StrokeReader::new();
 
-        Ok(Ui {
-            terminal,
-            app: app,
+        let mut ui = Ui::headless(db, app, reader);
+        ui.terminal = Some(terminal);
+        ui.tapefile = tapefile;
+        Ok(ui)
+    }
+
+    /// A Ui that isn't shown, so that a session can be run without a terminal, with strokes from
+    /// somewhere other than the keyboard.
+    pub fn headless(db: Db, app: Box<dyn App>, reader: Box<dyn StrokeSource>) -> Ui {
+        Ui {
+            terminal: None,
+            app,
             reader,
             db,
-            tapefile: tapefile,
-        })
+            tapefile: None,
+            started: get_now(),
+        }
     }
 
     pub fn run(&mut self, learn_time: Option<usize>) -> Result<()> {
@@ -75,24 +98,21 @@ impl Ui {
         loop {
             self.app.update_status(&mut self.db)?;
 
-            self.terminal.draw(|f| self.app.render(f))?;
+            self.draw()?;
 
             match self.reader.read_stroke(Duration::from_secs(1))? {
-                Value::Stroke(stroke) => {
+                Value::Stroke(stroke, time) => {
                     if let Some(tf) = &mut self.tapefile {
-                        writeln!(tf, "{}", stroke.to_tape())?;
+                        writeln!(tf, "{} {:.3}", stroke.to_tape(), time - self.started)?;
                     }
 
-                    if self.app.add_stroke(stroke, &mut self.db)? {
+                    if self.app.add_stroke(stroke, time, &mut self.db)? {
                         break;
                     }
                 }
-                Value::Resize(width, height) => self.terminal.resize(Rect {
-                    x: 1,
-                    y: 1,
-                    width,
-                    height,
-                })?,
+                Value::Invalid(diag) => self.app.show_problem(format!("Unknown stroke {}", diag)),
+                Value::Unexpected(text) => self.app.show_problem(text),
+                Value::Resize(width, height) => self.resize(width, height)?,
                 Value::Exit => break,
                 Value::Timeout => (),
             }
@@ -100,13 +120,50 @@ impl Ui {
         self.db.stop_timestamp(stamp_id)?;
         Ok(())
     }
+
+    /// Show the app until Escape is pressed.  Nothing written is given to the app, and the time
+    /// isn't counted as practice.
+    pub fn view(&mut self) -> Result<()> {
+        loop {
+            self.draw()?;
+
+            match self.reader.read_stroke(Duration::from_secs(1))? {
+                Value::Resize(width, height) => self.resize(width, height)?,
+                Value::Exit => break,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        if let Some(terminal) = &mut self.terminal {
+            terminal.draw(|f| self.app.render(f))?;
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u16, height: u16) -> Result<()> {
+        if let Some(terminal) = &mut self.terminal {
+            terminal.resize(Rect {
+                x: 1,
+                y: 1,
+                width,
+                height,
+            })?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Ui {
     fn drop(&mut self) {
-        disable_raw_mode().unwrap();
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).unwrap();
-        self.terminal.show_cursor().unwrap();
+        if let Some(terminal) = &mut self.terminal {
+            self.reader.stop();
+            disable_raw_mode().unwrap();
+            execute!(terminal.backend_mut(), LeaveAlternateScreen).unwrap();
+            terminal.show_cursor().unwrap();
+        }
 
         if let Some(message) = self.app.goodbye_ref() {
             println!("{}", message);
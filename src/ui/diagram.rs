@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: GPL-3.0
+//! A diagram of the steno keyboard.
+//!
+//! The diagram shows a stroke as the keys pressed on a picture of the keyboard, laid out by the
+//! current steno system.  For the English system:
+//!
+//! ```text
+//! ___________#_____________
+//! │S│T│P│H│ │*│ │F│P│L│T│D│
+//! │S│K│W│R│ │*│ │R│B│G│S│Z│
+//! ╰─┴─┴─┴─╯ ╰─╯ ╰─┴─┴─┴─┴─╯
+//!       │A│O│ │E│U│
+//!       ╰─┴─╯ ╰─┴─╯
+//! ```
+//!
+//! When a stroke that was written is given as well, the keys that were missed and the keys that
+//! were pressed but shouldn't have been are each shown in their own style.
+//!
+//! A diagram can also be a heatmap, with each key shaded by how often it is written wrong.
+
+use crate::errors::Shade;
+use crate::stroke::{system, Stroke};
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Widget},
+};
+
+pub struct Diagram<'a> {
+    rows: Vec<Vec<(char, Option<Stroke>)>>,
+    shading: Shading<'a>,
+    block: Option<Block<'a>>,
+}
+
+enum Shading<'a> {
+    Stroke {
+        expected: Stroke,
+        written: Option<Stroke>,
+    },
+    Heat(Box<dyn Fn(Stroke) -> Shade + 'a>),
+}
+
+impl<'a> Diagram<'a> {
+    /// A diagram showing the `expected` stroke.
+    pub fn new(expected: Stroke) -> Diagram<'a> {
+        Diagram {
+            rows: system::current().diagram(),
+            shading: Shading::Stroke {
+                expected,
+                written: None,
+            },
+            block: None,
+        }
+    }
+
+    /// A heatmap, with each key shaded by its rate of errors.
+    pub fn heatmap(rate: impl Fn(Stroke) -> Option<f64> + 'a) -> Diagram<'a> {
+        Diagram {
+            rows: system::current().diagram(),
+            shading: Shading::Heat(Box::new(move |key| Shade::new(rate(key)))),
+            block: None,
+        }
+    }
+
+    /// Compare the stroke that was written with the expected one.
+    pub fn written(mut self, stroke: Stroke) -> Diagram<'a> {
+        if let Shading::Stroke { written, .. } = &mut self.shading {
+            *written = Some(stroke);
+        }
+        self
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Diagram<'a> {
+        self.block = Some(block);
+        self
+    }
+
+    /// The number of lines needed to show the diagram, including the block around it.
+    pub fn height() -> u16 {
+        system::current().diagram().len() as u16 + 2
+    }
+
+    /// The width needed to show the diagram, including the block around it.
+    pub fn width() -> u16 {
+        let rows = system::current().diagram();
+        rows.iter().map(|row| row.len()).max().unwrap_or(0) as u16 + 2
+    }
+
+    // The style to show a key with.
+    fn key_style(&self, key: Stroke) -> Style {
+        let (expected, written) = match &self.shading {
+            Shading::Stroke { expected, written } => (*expected, *written),
+            Shading::Heat(shade) => {
+                let style = Style::default().fg(Color::Black);
+                return match shade(key) {
+                    Shade::Unknown => Style::default().fg(Color::DarkGray),
+                    Shade::Good => style.bg(Color::Green),
+                    Shade::Fair => style.bg(Color::Yellow),
+                    Shade::Weak => style.bg(Color::LightRed),
+                    Shade::Poor => style.bg(Color::Red),
+                };
+            }
+        };
+        let expected = expected.has_any(key);
+        let written = written.map_or(expected, |w| w.has_any(key));
+        match (expected, written) {
+            (true, true) => Style::default().add_modifier(Modifier::REVERSED),
+            // Missed keys.
+            (true, false) => Style::default().fg(Color::Black).bg(Color::Yellow),
+            // Extra keys.
+            (false, true) => Style::default().fg(Color::Black).bg(Color::Red),
+            (false, false) => Style::default().fg(Color::DarkGray),
+        }
+    }
+}
+
+impl<'a> Widget for Diagram<'a> {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let area = match self.block.take() {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+
+        for (row, y) in self.rows.iter().zip(area.top()..area.bottom()) {
+            for (&(ch, key), x) in row.iter().zip(area.left()..area.right()) {
+                let style = match key {
+                    Some(key) => self.key_style(key),
+                    None => Style::default(),
+                };
+                buf.get_mut(x, y).set_char(ch).set_style(style);
+            }
+        }
+    }
+}
+
+#[test]
+fn missed_and_extra() {
+    let mut buf = Buffer::empty(Rect::new(0, 0, 25, 6));
+    let expected = Stroke::from_text("KAT").unwrap();
+    let written = Stroke::from_text("KAS").unwrap();
+    Diagram::new(expected).written(written).render(buf.area, &mut buf);
+
+    let cell = |x, y| {
+        let cell = buf.get(x, y);
+        (cell.symbol.as_str(), cell.style())
+    };
+    assert_eq!(cell(3, 2).0, "K");
+    assert_eq!(cell(3, 2).1.add_modifier, Modifier::REVERSED);
+    assert_eq!(cell(21, 1).0, "T");
+    assert_eq!(cell(21, 1).1.bg, Some(Color::Yellow));
+    assert_eq!(cell(21, 2).0, "S");
+    assert_eq!(cell(21, 2).1.bg, Some(Color::Red));
+    assert_eq!(cell(1, 1).1.fg, Some(Color::DarkGray));
+}
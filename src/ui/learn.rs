@@ -2,7 +2,9 @@
 //! The textual ui.
 
 use crate::db::{get_now, Db, Work};
-use crate::stroke::{Stroke, StenoWord};
+use crate::dict::Dictionary;
+use crate::stroke::{Stroke, StenoPhrase, StenoWord};
+use super::diagram::Diagram;
 use super::{App, NewList, UiBackend};
 use anyhow::Result;
 use std::{
@@ -28,10 +30,7 @@ enum Source {
 impl Source {
     /// Should we update the record when written successfully?
     fn update_good(&self) -> bool {
-        match self {
-            Source::Learn(_) => true,
-            _ => false,
-        }
+        matches!(self, Source::Learn(_))
     }
 }
 
@@ -65,15 +64,31 @@ pub struct LearnApp {
     // Strokes so far, but including all corrections.
     raw_strokes: Vec<Stroke>,
 
+    // When each of the raw strokes was written.
+    stroke_times: Vec<f64>,
+
     // These are the strokes the user is expected to write.
     expected: Vec<Stroke>,
 
+    // Alternate outlines that are also accepted.
+    accepted: Vec<Vec<Stroke>>,
+
+    // When set, writing an alternate outline is accepted, but noted as not preferred, and the
+    // word stays at its current interval.
+    prefer_canonical: bool,
+
     // The current position.  Depending on learn mode, used to indicate where the learning should
     // start.
     pos: usize,
 
     help: Option<String>,
 
+    // The note from the lesson about the current word, shown along with a hint.
+    note: Option<String>,
+
+    // A note about the previous word, shown along with the help.
+    notice: Option<String>,
+
     // Did the user have to correct the currently written stroke?
     corrected: usize,
 
@@ -108,24 +123,34 @@ pub struct LearnApp {
 
     // The time this invocation was started (needed to show the display).
     start_time: f64,
+    // When the last word was finished, and so when the current one was shown.
     last_time: f64,
 
     // A goodbye message.
     goodbye: Option<String>,
+
+    // Dictionaries to show what the strokes would have written.  Empty if none were given.
+    dict: Dictionary,
 }
 
 impl LearnApp {
-    pub fn new_learn(new: Vec<NewList>, limit: Option<usize>) -> LearnApp {
+    pub fn new_learn(new: Vec<NewList>, limit: Option<usize>, prefer_canonical: bool) -> LearnApp {
         let start_time = get_now();
         LearnApp {
             start_time,
             last_time: start_time,
             limit,
+            prefer_canonical,
             source: Rc::new(Source::Learn(new)),
             ..LearnApp::default()
         }
     }
 
+    /// Show what is written as translated by the given dictionaries.
+    pub fn with_dictionary(self, dict: Dictionary) -> LearnApp {
+        LearnApp { dict, ..self }
+    }
+
     pub fn new_drill(list: usize) -> LearnApp {
         let start_time = get_now();
         LearnApp {
@@ -139,6 +164,10 @@ impl LearnApp {
 }
 
 impl App for LearnApp {
+    fn show_problem(&mut self, text: String) {
+        self.notice = Some(text);
+    }
+
     fn set_learntime(&mut self, learn_time: Option<usize>) {
         self.learn_time = learn_time;
     }
@@ -202,7 +231,7 @@ impl App for LearnApp {
     /// Add a single stroke that the user has written.  If it matches, will call 'update' to
     /// move to the next thing to write.  Otherwise, status will remain, showing the user any
     /// errors.  Will return Ok(true) if we have run out of things to do.
-    fn add_stroke(&mut self, stroke: Stroke, db: &mut Db) -> Result<bool> {
+    fn add_stroke(&mut self, stroke: Stroke, time: f64, db: &mut Db) -> Result<bool> {
         // The tape always records the strokes, as written.  Store in the tape before any kind of
         // processing.
         self.tape.push_front(stroke);
@@ -217,10 +246,24 @@ impl App for LearnApp {
             self.sofar.push(stroke);
         }
         self.raw_strokes.push(stroke);
+        self.stroke_times.push(time);
+
+        if self.expected == self.sofar || self.accepted.contains(&self.sofar) {
+            // Was this written with one of the alternates, when the canonical outline is
+            // preferred?
+            let not_preferred = self.prefer_canonical && self.expected != self.sofar;
+            self.notice = None;
+
+            // Record how long the word took to start, and each pause between its strokes.
+            if let Some(head) = &self.head {
+                let first = self.stroke_times[0] - self.last_time;
+                let gaps: Vec<f64> =
+                    self.stroke_times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+                db.record_latency(head, first, &gaps, self.corrected)?;
+            }
 
-        if self.expected == self.sofar {
             // Update the WPM.
-            let now = get_now();
+            let now = time;
             let new_wpm = 60.0 / (now - self.last_time);
             self.last_time = now;
             self.wpm = self.factor * self.wpm +
@@ -230,7 +273,14 @@ impl App for LearnApp {
             self.factor = 1.0 - ((0.95 - self.factor) * 0.9 + 0.05);
 
             // Written correctly, record this, and update.
-            if self.source.update_good() || self.corrected > 0 {
+            if not_preferred && self.corrected == 0 {
+                let head = self.head.as_ref().unwrap();
+                if self.source.update_good() {
+                    db.keep(head)?;
+                }
+                self.notice = Some(format!("{}: accepted, but {} is preferred",
+                        head.display(), head.strokes));
+            } else if self.source.update_good() || self.corrected > 0 {
                 let head = self.head.as_ref().unwrap();
 
                 // Determine the actual interval.  If this is a new word, just use zero.
@@ -276,16 +326,11 @@ impl App for LearnApp {
             Ok(false)
         } else {
             // Check for any errors, and show a hint if that happens.
-            let mut show = false;
-            for (&a, &b) in self.expected.iter().zip(&self.sofar) {
-                if a != b {
-                    show = true;
-                }
-
-                if show {
-                    let strokes = StenoWord(self.expected.clone());
-                    self.help = Some(format!("Should be written as {}", strokes));
-                }
+            let target = self.target();
+            if self.sofar.iter().zip(target).any(|(a, b)| a != b) {
+                let strokes = StenoWord(self.expected.clone());
+                self.help = Some(format!("Should be written as {}", strokes));
+                self.note = self.head.as_ref().and_then(|head| head.note.clone());
             }
             Ok(false)
         }
@@ -303,6 +348,8 @@ impl App for LearnApp {
                 Constraint::Length(12),
                 Constraint::Length(3),
                 Constraint::Length(3),
+                Constraint::Length(if self.dict.is_empty() { 0 } else { 4 }),
+                Constraint::Length(Diagram::height()),
                 Constraint::Max(70),
             ])
             .split(top[0]);
@@ -312,11 +359,11 @@ impl App for LearnApp {
             .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
             .split(left[0]);
 
-        let lstatus = List::new(self.status.as_ref())
+        let lstatus = List::new(self.status.as_slice())
             .block(Block::default().title("Status").borders(Borders::ALL));
         f.render_widget(lstatus, status[0]);
 
-        let rstatus = List::new(self.rstatus.as_ref())
+        let rstatus = List::new(self.rstatus.as_slice())
             .block(Block::default().title("Totals").borders(Borders::ALL));
         f.render_widget(rstatus, status[1]);
 
@@ -330,12 +377,13 @@ impl App for LearnApp {
         f.render_widget(exercise, left[1]);
 
         let mut spans = vec![];
+        let target = self.target();
         for (id, &stroke) in self.sofar.iter().enumerate() {
             if id > 0 {
                 spans.push(Span::raw(" / "));
             }
             let textual = format!("{}", stroke);
-            if id >= self.expected.len() || stroke != self.expected[id] {
+            if id >= target.len() || stroke != target[id] {
                 spans.push(Span::styled(textual, Style::default().add_modifier(Modifier::REVERSED)));
             } else {
                 spans.push(Span::raw(textual));
@@ -345,13 +393,51 @@ impl App for LearnApp {
             .block(Block::default().title("Strokes").borders(Borders::ALL));
         f.render_widget(strokes, left[2]);
 
+        // The diagram shows the next stroke to write, or, once a stroke is wrong, how it differs
+        // from the one that should have been written.
+        let wrong = self
+            .sofar
+            .iter()
+            .enumerate()
+            .find(|&(id, stroke)| target.get(id) != Some(stroke));
+        let diagram = match wrong {
+            Some((id, &stroke)) => {
+                Diagram::new(target.get(id).copied().unwrap_or_default()).written(stroke)
+            }
+            None => Diagram::new(target.get(self.sofar.len()).copied().unwrap_or_default()),
+        };
+        let diagram = diagram.block(Block::default().title("Diagram").borders(Borders::ALL));
+        f.render_widget(diagram, left[4]);
+
+        // The translation of what was written recently, and, once a stroke is wrong, what the
+        // strokes for this word wrote.
+        if !self.dict.is_empty() {
+            let mut items = vec![ListItem::new(self.recent_translation())];
+            if let (Some(_), Some(head)) = (wrong, &self.head) {
+                items.push(ListItem::new(format!(
+                    "You wrote '{}' instead of '{}'",
+                    self.dict.translate(&self.sofar).join(" "),
+                    head.text
+                )));
+            }
+            let translation = List::new(items)
+                .block(Block::default().title("Translation").borders(Borders::ALL));
+            f.render_widget(translation, left[3]);
+        }
+
         let mut items = vec![];
         if let Some(text) = &self.help {
             items.push(ListItem::new(text.as_ref()));
+            if let Some(note) = &self.note {
+                items.push(ListItem::new(note.as_ref()));
+            }
+        }
+        if let Some(text) = &self.notice {
+            items.push(ListItem::new(text.as_ref()));
         }
         let help = List::new(items.as_slice())
             .block(Block::default().title("Help").borders(Borders::ALL));
-        f.render_widget(help, left[3]);
+        f.render_widget(help, left[5]);
 
         // Render the tape.
         let mut items = vec![];
@@ -374,6 +460,36 @@ impl App for LearnApp {
 }
 
 impl LearnApp {
+    // The outline the user appears to be writing.  This is the first accepted outline that what
+    // has been written so far is the start of, or the canonical outline if none are.
+    fn target(&self) -> &[Stroke] {
+        std::iter::once(&self.expected)
+            .chain(&self.accepted)
+            .find(|outline| outline.starts_with(&self.sofar))
+            .unwrap_or(&self.expected)
+    }
+
+    // The translation of the last few strokes on the tape, after the undo strokes have removed
+    // the strokes before them.
+    fn recent_translation(&self) -> String {
+        let mut strokes = vec![];
+        for &stroke in self.tape.iter().take(20).rev() {
+            if stroke.is_star() {
+                strokes.pop();
+            } else {
+                strokes.push(stroke);
+            }
+        }
+        let words = self.dict.translate(&strokes);
+        words[words.len().saturating_sub(8)..].join(" ")
+    }
+
+    // Set the outlines expected for the given work.
+    fn set_expected(&mut self, work: &Work) {
+        self.expected = work.strokes.linear();
+        self.accepted = work.alternates.iter().map(|alt| alt.linear()).collect();
+    }
+
     // Update the app with the current progress.  Returns true if we should exit.
     fn update_learn(&mut self, db: &mut Db, new: &[NewList]) -> Result<bool> {
         let words = db.get_learns(1)?;
@@ -381,9 +497,11 @@ impl LearnApp {
         self.text.clear();
         self.sofar.clear();
         self.raw_strokes.clear();
+        self.stroke_times.clear();
         self.expected.clear();
         self.corrected = 0;
         self.help = None;
+        self.note = None;
 
         let mut new_word = false;
         if words.is_empty() {
@@ -394,9 +512,9 @@ impl LearnApp {
                 }
             }
             if !new.is_empty() {
-                if let Some(work) = db.get_new(&new)? {
-                    self.expected.append(&mut work.strokes.linear());
-                    self.text.push_str(&work.text);
+                if let Some(work) = db.get_new(new)? {
+                    self.set_expected(&work);
+                    self.text.push_str(work.display());
                     self.head = Some(work);
                     self.new_words += 1;
                     new_word = true;
@@ -413,9 +531,9 @@ impl LearnApp {
                 if id > 0 {
                     self.text.push(' ');
                 }
-                self.text.push_str(&word.text);
+                self.text.push_str(word.display());
                 if id == 0 {
-                    self.expected.append(&mut word.strokes.linear());
+                    self.set_expected(word);
                     self.text.push_str(" |");
                 }
             }
@@ -428,9 +546,15 @@ impl LearnApp {
 
         if let Some(work) = &self.head {
             if work.interval < 90.0 {
-                self.help = Some(format!("{}write: {}",
+                self.help = Some(format!("{}write: {}{}",
                         if new_word { "New word, " } else { "" },
-                        work.strokes));
+                        work.strokes,
+                        if work.alternates.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" (or {})", StenoPhrase::join_list(&work.alternates))
+                        }));
+                self.note = work.note.clone();
             }
         }
 
@@ -446,17 +570,20 @@ impl LearnApp {
 
         self.text.clear();
         self.sofar.clear();
+        self.raw_strokes.clear();
+        self.stroke_times.clear();
         self.expected.clear();
         self.corrected = 0;
         self.help = None;
+        self.note = None;
 
         for (id, word) in words.iter().enumerate() {
             if id > 0 {
                 self.text.push(' ');
             }
-            self.text.push_str(&word.text);
+            self.text.push_str(word.display());
             if id == 0 {
-                self.expected.append(&mut word.strokes.linear());
+                self.set_expected(word);
             }
             if let Some(head) = words.first() {
                 self.head = Some(head.clone());
@@ -468,3 +595,55 @@ impl LearnApp {
         Ok(false)
     }
 }
+
+#[test]
+fn scripted_session() {
+    use crate::input::Script;
+    use crate::lessons::{Entry, Lesson};
+    use crate::ui::Ui;
+
+    let path = std::env::temp_dir().join(format!("sdrill-session-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    Db::init(&path, "english").unwrap();
+    let mut db = Db::open(&path).unwrap();
+    let entry = |word: &str, steno| {
+        Entry::new(word.to_string(), StenoPhrase::parse(steno).unwrap(), vec![])
+    };
+    db.load(&Lesson {
+        description: "Animals".to_string(),
+        entries: vec![entry("cat", "KAT"), entry("dog", "TKOG")],
+        warnings: vec![],
+    })
+    .unwrap();
+
+    // Write "cat" correctly, then correct a mistake in "dog".
+    let app = LearnApp::new_learn(vec![NewList { list: 1, factor: 0.0 }], Some(2), false);
+    let script = Script::parse("KAT TKOPBG * TKOG").unwrap();
+    let mut ui = Ui::headless(db, Box::new(app), Box::new(script));
+    ui.run(None).unwrap();
+    drop(ui);
+
+    let mut db = Db::open(&path).unwrap();
+    assert_eq!(db.get_learned().unwrap(), [("KAT".to_string(), 1)]);
+    let errors = db.get_errors(None).unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].steno.as_str(), errors[0].actual.as_str()), ("TKOG", "TKOPBG/*/TKOG"));
+    // Only "cat" was written without corrections, so only its time counts.
+    let latencies = db.get_latencies(1.0).unwrap();
+    assert_eq!(latencies.len(), 1);
+    assert_eq!(latencies[0].steno, "KAT");
+    assert!(latencies[0].gaps.is_empty());
+    drop(db);
+
+    // A session run against a scratch copy, as when replaying, leaves the database as it was.
+    let app = LearnApp::new_drill(1);
+    let script = Script::parse("KAT TKOG").unwrap();
+    let mut ui = Ui::headless(Db::open_scratch(&path).unwrap(), Box::new(app), Box::new(script));
+    ui.run(None).unwrap();
+    drop(ui);
+    let mut db = Db::open(&path).unwrap();
+    assert_eq!(db.get_learned().unwrap(), [("KAT".to_string(), 1)]);
+    assert_eq!(db.get_latencies(1.0).unwrap().len(), 1);
+    drop(db);
+    std::fs::remove_file(&path).unwrap();
+}
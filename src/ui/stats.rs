@@ -0,0 +1,91 @@
+// SPDX-License-Identifier: GPL-3.0
+//! A screen showing the accuracy of each key.
+
+use crate::db::Db;
+use crate::errors::{Accuracy, SHADES};
+use crate::stroke::Stroke;
+use super::diagram::Diagram;
+use super::{App, UiBackend};
+use anyhow::Result;
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    terminal::Frame,
+    widgets::{Block, Borders, List, ListItem},
+};
+
+pub struct StatsApp {
+    accuracy: Accuracy,
+}
+
+impl StatsApp {
+    pub fn new(accuracy: Accuracy) -> StatsApp {
+        StatsApp { accuracy }
+    }
+}
+
+// Nothing is written on this screen, it is only shown until Escape is pressed.
+impl App for StatsApp {
+    fn update_status(&mut self, _db: &mut Db) -> Result<()> {
+        Ok(())
+    }
+
+    fn update(&mut self, _db: &mut Db) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn add_stroke(&mut self, _stroke: Stroke, _time: f64, _db: &mut Db) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn show_problem(&mut self, _text: String) {}
+
+    fn set_learntime(&mut self, _learn_time: Option<usize>) {}
+
+    fn goodbye_ref(&self) -> Option<&str> {
+        None
+    }
+
+    fn render(&mut self, f: &mut Frame<UiBackend>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(Diagram::height()), Constraint::Min(3)].as_ref())
+            .split(f.size());
+        let maps = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Length(Diagram::width()),
+                    Constraint::Length(Diagram::width()),
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
+            .split(rows[0]);
+
+        let accuracy = &self.accuracy;
+        let missed = Diagram::heatmap(|key| accuracy.missed(key))
+            .block(Block::default().title("Missed").borders(Borders::ALL));
+        f.render_widget(missed, maps[0]);
+        let extra = Diagram::heatmap(|key| accuracy.extra(key))
+            .block(Block::default().title("Pressed by mistake").borders(Borders::ALL));
+        f.render_widget(extra, maps[1]);
+
+        let mut items = vec![
+            ListItem::new(format!("Shading: {}", SHADES)),
+            ListItem::new(""),
+            ListItem::new("  key  missed  extra"),
+        ];
+        let height = rows[1].height.saturating_sub(2 + items.len() as u16) as usize;
+        for (key, missed, extra) in self.accuracy.weakest(height) {
+            items.push(ListItem::new(format!(
+                "  {:>4} {:>5.1}% {:>5.1}%",
+                key.to_string(),
+                missed * 100.0,
+                extra * 100.0
+            )));
+        }
+        let weakest = List::new(items)
+            .block(Block::default().title("Weakest keys").borders(Borders::ALL));
+        f.render_widget(weakest, rows[1]);
+    }
+}